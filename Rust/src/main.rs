@@ -1,5 +1,11 @@
 mod year_2019;
 
 fn main() {
-	year_2019::main();
+	// Any arguments are a request to run one of the tools instead of the days
+	let args :Vec<String> = std::env::args().skip(1).collect();
+	if args.is_empty() {
+		year_2019::main();
+	} else {
+		year_2019::tools(&args);
+	}
 }
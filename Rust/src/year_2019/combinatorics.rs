@@ -0,0 +1,216 @@
+/**
+ * Iterators for the days that need to search through arrangements of values.
+ *
+ * Everything works on indices into the slice it was given, and hands back a
+ * fresh Vec of the chosen items each time.
+ */
+
+
+// Every ordering of the items, in lexicographic order of their positions. If
+// the slice is sorted then that's lexicographic order of the items too.
+//
+// Heap's algorithm is a little cheaper per step, but it jumps around so the
+// order wouldn't be lexicographic. Instead each step finds the next ordering
+// with the classic swap-and-reverse.
+pub struct Permutations<'a, T :'a> {
+	items : &'a [T],
+	indices : Vec<usize>,
+	done : bool,
+}
+
+
+impl<'a, T :Clone> Iterator for Permutations<'a, T> {
+	type Item = Vec<T>;
+	
+	fn next(&mut self) -> Option<Vec<T>> {
+		if self.done {
+			return None;
+		}
+		let current = self.indices.iter().map(|&idx| self.items[idx].clone()).collect();
+		
+		// Find the rightmost position that can still be increased
+		let len = self.indices.len();
+		let pivot = (1..len).rev().find(|&idx| self.indices[idx - 1] < self.indices[idx]);
+		match pivot {
+			None => self.done = true,
+			Some(pivot) => {
+				// Swap it with the smallest larger value to its right, then put
+				// everything after it back in ascending order
+				let pivot = pivot - 1;
+				let mut swap = len - 1;
+				while self.indices[swap] <= self.indices[pivot] {
+					swap -= 1;
+				}
+				self.indices.swap(pivot, swap);
+				self.indices[pivot + 1..].reverse();
+			},
+		}
+		
+		return Some(current);
+	}
+}
+
+
+pub fn permutations<'a, T :Clone>(items :&'a [T]) -> Permutations<'a, T> {
+	return Permutations {
+		items,
+		indices : (0..items.len()).collect(),
+		done : false,
+	};
+}
+
+
+// Every way of choosing k of the items, keeping them in their original order
+#[allow(dead_code)]
+pub struct Combinations<'a, T :'a> {
+	items : &'a [T],
+	indices : Vec<usize>,
+	done : bool,
+}
+
+
+impl<'a, T :Clone> Iterator for Combinations<'a, T> {
+	type Item = Vec<T>;
+	
+	fn next(&mut self) -> Option<Vec<T>> {
+		if self.done {
+			return None;
+		}
+		let current = self.indices.iter().map(|&idx| self.items[idx].clone()).collect();
+		
+		// Find the rightmost index that hasn't reached its last possible
+		// position, bump it, and reset everything after it
+		let k = self.indices.len();
+		let n = self.items.len();
+		match (0..k).rev().find(|&pos| self.indices[pos] != pos + n - k) {
+			None => self.done = true,
+			Some(pos) => {
+				self.indices[pos] += 1;
+				for next in pos + 1..k {
+					self.indices[next] = self.indices[next - 1] + 1;
+				}
+			},
+		}
+		
+		return Some(current);
+	}
+}
+
+
+#[allow(dead_code)]
+pub fn combinations<'a, T :Clone>(items :&'a [T], k :usize) -> Combinations<'a, T> {
+	return Combinations {
+		items,
+		indices : (0..k).collect(),
+		done : k > items.len(),
+	};
+}
+
+
+// Every way of picking one item from each of the lists, with the last list
+// changing fastest
+pub struct CartesianProduct<'a, T :'a> {
+	lists : Vec<&'a [T]>,
+	indices : Vec<usize>,
+	done : bool,
+}
+
+
+impl<'a, T :Clone> Iterator for CartesianProduct<'a, T> {
+	type Item = Vec<T>;
+	
+	fn next(&mut self) -> Option<Vec<T>> {
+		if self.done {
+			return None;
+		}
+		let current = self.indices.iter()
+			.zip(self.lists.iter())
+			.map(|(&idx, list)| list[idx].clone())
+			.collect();
+		
+		// Count up like an odometer
+		self.done = true;
+		for pos in (0..self.indices.len()).rev() {
+			self.indices[pos] += 1;
+			if self.indices[pos] < self.lists[pos].len() {
+				self.done = false;
+				break;
+			}
+			self.indices[pos] = 0;
+		}
+		
+		return Some(current);
+	}
+}
+
+
+pub fn cartesian_product<'a, T :Clone>(lists :&[&'a [T]]) -> CartesianProduct<'a, T> {
+	return CartesianProduct {
+		lists : lists.to_vec(),
+		indices : vec![0; lists.len()],
+		done : lists.iter().any(|list| list.is_empty()),
+	};
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	#[test]
+	fn permutations_in_order() {
+		let found :Vec<Vec<isize>> = permutations(&[1, 2, 3]).collect();
+		assert_eq!(found, vec![
+			vec![1, 2, 3], vec![1, 3, 2], vec![2, 1, 3],
+			vec![2, 3, 1], vec![3, 1, 2], vec![3, 2, 1],
+		]);
+		assert_eq!(permutations(&[0, 1, 2, 3, 4]).count(), 120);
+	}
+	
+	#[test]
+	fn permutations_edge_cases() {
+		// There's exactly one way to order nothing
+		assert_eq!(permutations::<isize>(&[]).collect::<Vec<_>>(), vec![Vec::new()]);
+		assert_eq!(permutations(&[7]).collect::<Vec<_>>(), vec![vec![7]]);
+		
+		// Equal items are still told apart by position
+		assert_eq!(permutations(&[5, 5]).collect::<Vec<_>>(), vec![vec![5, 5], vec![5, 5]]);
+	}
+	
+	#[test]
+	fn combinations_in_order() {
+		let found :Vec<Vec<isize>> = combinations(&[1, 2, 3, 4], 2).collect();
+		assert_eq!(found, vec![
+			vec![1, 2], vec![1, 3], vec![1, 4],
+			vec![2, 3], vec![2, 4], vec![3, 4],
+		]);
+	}
+	
+	#[test]
+	fn combinations_edge_cases() {
+		// Choosing nothing can be done one way, even from nothing
+		assert_eq!(combinations(&[1, 2, 3], 0).collect::<Vec<_>>(), vec![Vec::new()]);
+		assert_eq!(combinations::<isize>(&[], 0).collect::<Vec<_>>(), vec![Vec::new()]);
+		
+		// Choosing all of them can only be done one way, and more can't be
+		// done at all
+		assert_eq!(combinations(&[1, 2, 3], 3).collect::<Vec<_>>(), vec![vec![1, 2, 3]]);
+		assert_eq!(combinations(&[1, 2, 3], 4).count(), 0);
+		assert_eq!(combinations::<isize>(&[], 1).count(), 0);
+	}
+	
+	#[test]
+	fn cartesian_product_in_order() {
+		let found :Vec<Vec<isize>> = cartesian_product(&[&[1, 2][..], &[3], &[4, 5]]).collect();
+		assert_eq!(found, vec![vec![1, 3, 4], vec![1, 3, 5], vec![2, 3, 4], vec![2, 3, 5]]);
+	}
+	
+	#[test]
+	fn cartesian_product_edge_cases() {
+		// Nothing to pick from gives one empty pick, but any empty list means
+		// there's nothing at all
+		assert_eq!(cartesian_product::<isize>(&[]).collect::<Vec<_>>(), vec![Vec::new()]);
+		assert_eq!(cartesian_product(&[&[1, 2][..], &[]]).count(), 0);
+		assert_eq!(cartesian_product(&[&[][..], &[1, 2]]).count(), 0);
+	}
+}
@@ -34,16 +34,13 @@
  */
 
 
-use std::error::Error;
-
-
 // Load the inputs into a vector
 fn load_inputs() -> Vec<u64>
 {
 	// Load the inputs from file
 	let input = match std::fs::read_to_string("inputs/day1.txt")
 	{
-		Err(error) => panic!("Failed to open inputs: {}", error.description()),
+		Err(error) => panic!("Failed to open inputs: {}", error),
 		Ok(string) => string,
 	};
 	
@@ -43,8 +43,8 @@
  */
 
 
-use std::error::Error;
-use super::intcode::Interpreter;
+use super::combinatorics;
+use super::intcode::{Interpreter, StepResult};
 
 
 // Load the input into a buffer to be treated as Intcode RAM
@@ -53,7 +53,7 @@ fn load_input() -> String
 	// Load the input from file
 	let input = match std::fs::read_to_string("inputs/day2.txt")
 	{
-		Err(error) => panic!("Failed to open input: {}", error.description()),
+		Err(error) => panic!("Failed to open input: {}", error),
 		Ok(string) => string,
 	};
 	
@@ -64,14 +64,14 @@ fn load_input() -> String
 fn run_part1() -> isize
 {
 	// Load the program as provided
-	let mut program = Interpreter::load(&load_input());
+	let mut program = Interpreter::load(&load_input()).unwrap();
 	
 	// Modify it as required
 	program.set(1, 12);
 	program.set(2, 2);
 	
 	// Run it
-	program.run();
+	program.run().unwrap();
 	
 	// Return the first value
 	return program.get(0);
@@ -82,39 +82,206 @@ pub fn main()
 {
 	println!("Day2:");
 	println!("\tPart1 = {}", run_part1());
-	println!("\tPart2 = {}", run_part2());
+	let part2 = run_part2();
+	println!("\tPart2 = {} (noun {}, verb {})", part2.code(), part2.noun, part2.verb);
 }
 
 
-fn run_part2() -> isize
+// The output we're looking for in part 2
+const TARGET :isize = 19690720;
+
+// Each of the inputs is between 0 and 99 inclusive
+const MAX_INPUT :isize = 99;
+
+// Way more than any sensible noun and verb should need, so that a bad pair
+// can't hang the search
+const MAX_STEPS :usize = 100000;
+
+
+// Inputs that make the program produce the target
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct NounVerb
 {
-	// Load the program as provided
-	let input = load_input();
+	noun : isize,
+	verb : isize,
+}
+
+
+impl NounVerb
+{
+	// The combined value that the puzzle wants
+	fn code(self :&NounVerb) -> isize
+	{
+		return self.noun * 100 + self.verb;
+	}
+}
+
+
+// Run the program with the given inputs, returning what ends up at address 0,
+// or None if it couldn't run to completion
+fn evaluate(base :&Interpreter, noun :isize, verb :isize) -> Option<isize>
+{
+	let mut program = base.clone();
+	program.set(1, noun);
+	program.set(2, verb);
+	program.enable_cycle_detection();
+	return match program.run_with_limit(MAX_STEPS)
+	{
+		Ok(StepResult::Break) => Some(program.get(0)),
+		_ => None,
+	};
+}
+
+
+// If address 0 ends up as c + a * noun + b * verb, solve for the target
+// directly. Three points are enough to fit it, and a few more are checked to
+// make sure it really is affine before trusting it. Returns None if it isn't,
+// or if the answer it comes up with doesn't actually work, so that something
+// else can have a go. Anything that overflows can't be affine either.
+fn solve_affine(base :&Interpreter) -> Option<Option<NounVerb>>
+{
+	// Fit it
+	let c = evaluate(base, 0, 0)?;
+	let a = evaluate(base, 1, 0)?.checked_sub(c)?;
+	let b = evaluate(base, 0, 1)?.checked_sub(c)?;
+	let predict = |noun :isize, verb :isize| -> Option<isize>
+	{
+		return c.checked_add(a.checked_mul(noun)?)?.checked_add(b.checked_mul(verb)?);
+	};
 	
-	// Brute force it
-	for noun in 0..99
+	// Check it, bailing out if any point doesn't fit
+	let checks = [(1, 1), (MAX_INPUT, 0), (0, MAX_INPUT), (MAX_INPUT, MAX_INPUT), (37, 58), (64, 21)];
+	for &(noun, verb) in checks.iter()
 	{
-		for verb in 0..99
+		if evaluate(base, noun, verb)? != predict(noun, verb)?
 		{
-			// Create a new program from the input
-			let mut program = Interpreter::load(&input);
-			
-			// Mutate the program
-			program.set(1, noun);
-			program.set(2, verb);
-			
-			// Run it
-			program.run();
-			
-			// See if that was what we wanted
-			if program.get(0) == 19690720
+			return None;
+		}
+	}
+	
+	// Solve for the verb given each noun, keeping the same order as a sweep
+	// so that the smallest noun wins if there's more than one answer
+	for noun in 0..=MAX_INPUT
+	{
+		let remaining = TARGET.checked_sub(c)?.checked_sub(a.checked_mul(noun)?)?;
+		let verb = if b == 0
+		{
+			// The verb doesn't matter, so the first one will do
+			if remaining == 0 { Some(0) } else { None }
+		}
+		else if remaining.checked_rem(b)? == 0
+		{
+			Some(remaining.checked_div(b)?)
+		}
+		else
+		{
+			None
+		};
+		
+		if let Some(verb) = verb.filter(|verb| (0..=MAX_INPUT).contains(verb))
+		{
+			// Only a handful of points were checked, so make sure it really
+			// does give the target
+			if evaluate(base, noun, verb) != Some(TARGET)
 			{
-				// Return the combined value
-				return noun * 100 + verb;
+				return None;
 			}
+			return Some(Some(NounVerb { noun, verb }));
 		}
 	}
 	
-	panic!("Didn't find it?");
+	// It's affine, but there's no answer
+	return Some(None);
+}
+
+
+// Try every noun and verb pair, split across a worker per core. Each worker
+// takes a contiguous range of nouns and the first answer in noun order wins,
+// same as a serial sweep would.
+fn sweep(base :&Interpreter) -> Option<NounVerb>
+{
+	let nouns :Vec<isize> = (0..=MAX_INPUT).collect();
+	let verbs :Vec<isize> = (0..=MAX_INPUT).collect();
+	let workers = std::thread::available_parallelism().map_or(1, |count| count.get());
+	let chunk_size = nouns.len().div_ceil(workers);
+	
+	let results :Vec<Option<NounVerb>> = std::thread::scope(|scope|
+	{
+		let verbs = &verbs;
+		let handles :Vec<_> = nouns.chunks(chunk_size)
+			.map(|nouns| scope.spawn(move ||
+			{
+				return combinatorics::cartesian_product(&[nouns, verbs])
+					.map(|pair| NounVerb { noun : pair[0], verb : pair[1] })
+					.find(|pair| evaluate(base, pair.noun, pair.verb) == Some(TARGET));
+			}))
+			.collect();
+		return handles.into_iter()
+			.map(|handle| handle.join().expect("Sweep worker panicked"))
+			.collect();
+	});
+	
+	return results.into_iter().flatten().next();
+}
+
+
+// Solve it directly if possible, otherwise brute force it
+fn solve(base :&Interpreter) -> Option<NounVerb>
+{
+	return match solve_affine(base)
+	{
+		Some(found) => found,
+		None => sweep(base),
+	};
+}
+
+
+fn run_part2() -> NounVerb
+{
+	// Load the program as provided
+	let base = Interpreter::load(&load_input()).unwrap();
+	
+	return match solve(&base)
+	{
+		Some(found) => found,
+		None => panic!("Didn't find it?"),
+	};
 }
 
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	
+	const ANSWER :NounVerb = NounVerb { noun : MAX_INPUT, verb : MAX_INPUT };
+	
+	#[test]
+	fn affine()
+	{
+		// Address 0 ends up as 19680721 + 100 * noun + verb
+		let base = Interpreter::load("1101,0,0,30,1002,1,100,20,1,20,2,20,1001,20,19680721,0,99").unwrap();
+		assert_eq!(solve_affine(&base), Some(Some(ANSWER)));
+		assert_eq!(sweep(&base), Some(ANSWER));
+		assert_eq!(solve(&base), Some(ANSWER));
+	}
+	
+	#[test]
+	fn affine_without_answer()
+	{
+		// Address 0 just ends up as the noun
+		let base = Interpreter::load("1101,0,0,30,1001,1,0,0,99").unwrap();
+		assert_eq!(solve_affine(&base), Some(None));
+		assert_eq!(solve(&base), None);
+	}
+	
+	#[test]
+	fn not_affine()
+	{
+		// Address 0 ends up as 19680919 + noun * verb, so it has to be swept
+		let base = Interpreter::load("1101,0,0,30,2,1,2,20,1001,20,19680919,0,99").unwrap();
+		assert_eq!(solve_affine(&base), None);
+		assert_eq!(sweep(&base), Some(ANSWER));
+		assert_eq!(solve(&base), Some(ANSWER));
+	}
+}
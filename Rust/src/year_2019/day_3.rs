@@ -30,7 +30,6 @@
  * considered.
  */
 
-use std::error::Error;
 use std::ops::Add;
 use std::default::Default;
 
@@ -158,7 +157,7 @@ impl Wire {
 		// I don't know if this function actually helps at all...
 		for segment in &self.segments
 		{
-			lambda(&segment);
+			lambda(segment);
 		}
 	}
 }
@@ -171,7 +170,7 @@ fn load_wires() -> Vec<Wire>
 	// Load the input from file
 	let input = match std::fs::read_to_string("inputs/day3.txt")
 	{
-		Err(error) => panic!("Failed to open input: {}", error.description()),
+		Err(error) => panic!("Failed to open input: {}", error),
 		Ok(string) => string,
 	};
 	
@@ -222,7 +221,7 @@ fn smallest_extents(wire :&Wire) -> Extents
 
 
 // Combine 2 wires to make a grid they can both fit on
-fn combine_wires<T>(wires :&Vec<Wire>) -> Grid<T>
+fn combine_wires<T>(wires :&[Wire]) -> Grid<T>
 where
 	T: Copy + Clone + Default
 {
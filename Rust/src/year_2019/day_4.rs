@@ -17,9 +17,6 @@
  * meet these criteria?
  */
 
-use std::error::Error;
-
-
 
 #[derive(Copy, Clone)]
 struct Password(usize, usize, usize, usize, usize, usize);
@@ -32,7 +29,7 @@ impl Password {
 			(i / 1000) % 10,
 			(i / 100) % 10,
 			(i / 10) % 10,
-			(i / 1) % 10,
+			i % 10,
 		);
 		
 		// Round up to next valid one
@@ -92,7 +89,7 @@ fn has_part2_adjacents(pw :Password) -> bool {
 	// Brute force check of each pair and their surroundings
 	fn check_quad(a :usize, b :usize, c :usize, d :usize) -> bool {
 		return b == c && a != b && c != d;
-	};
+	}
 	// A value can never be 10, so use that for the edges
 	return
 		check_quad(10, pw.0, pw.1, pw.2) ||
@@ -137,7 +134,7 @@ fn get_range() -> [usize; 2]
 	// Load the input from file
 	let input = match std::fs::read_to_string("inputs/day4.txt")
 	{
-		Err(error) => panic!("Failed to open input: {}", error.description()),
+		Err(error) => panic!("Failed to open input: {}", error),
 		Ok(string) => string,
 	};
 	
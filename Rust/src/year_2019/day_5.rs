@@ -28,8 +28,8 @@
  */
 
 
-use std::error::Error;
 use super::intcode::{Interpreter, StepResult};
+use super::intcode::device::{Machine, QueueDevice};
 
 
 // Load the input into a buffer to be treated as Intcode RAM
@@ -38,7 +38,7 @@ fn load_input() -> String
 	// Load the input from file
 	let input = match std::fs::read_to_string("inputs/day5.txt")
 	{
-		Err(error) => panic!("Failed to open input: {}", error.description()),
+		Err(error) => panic!("Failed to open input: {}", error),
 		Ok(string) => string,
 	};
 	
@@ -47,43 +47,30 @@ fn load_input() -> String
 
 fn run_part1() -> isize
 {
-	// Load the program as provided
-	let mut program = Interpreter::load(&load_input());
+	// Load the program as provided, along with the input it wants
+	let program = Interpreter::load(&load_input()).unwrap();
+	let mut machine = Machine::new(program, QueueDevice::new(vec![1]));
 	
 	// Run it
-	let result = program.run();
-	assert_eq!(result, StepResult::Input);
-	
-	// Set the input it wants
-	program.set_input(1);
-	
-	// Continue on
-	let result = program.run();
-	assert_eq!(result, StepResult::Break);
+	let result = machine.run();
+	assert_eq!(result, Ok(StepResult::Break));
 	
 	// Return the last output value
-	let output = program.get_outputs();
-	return *output.last().unwrap();
+	return *machine.device.outputs.last().unwrap();
 }
 
 fn run_part2() -> isize
 {
-	// Load the program as provided
-	let mut program = Interpreter::load(&load_input());
+	// Load the program as provided, along with the input it wants
+	let program = Interpreter::load(&load_input()).unwrap();
+	let mut machine = Machine::new(program, QueueDevice::new(vec![5]));
 	
 	// Run it
-	let result = program.run();
-	assert_eq!(result, StepResult::Input);
-	
-	// Set the input it wants
-	program.set_input(5);
-	
-	// Continue on
-	let result = program.run();
-	assert_eq!(result, StepResult::Break);
+	let result = machine.run();
+	assert_eq!(result, Ok(StepResult::Break));
 	
 	// Return the single output
-	return program.get_outputs()[0];
+	return machine.device.outputs[0];
 }
 
 
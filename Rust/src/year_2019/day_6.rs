@@ -25,13 +25,13 @@
  */
 
 
-use std::error::Error;
 use std::rc::{Rc, Weak};
 use std::collections::HashMap;
 use std::cell::RefCell;
 
 
 struct Orbit {
+	#[allow(dead_code)]
 	parent :Weak<Orbit>,
 	name :String,
 	children :RefCell<Vec<Rc<Orbit>>>,
@@ -54,7 +54,7 @@ fn load_inputs() -> Rc<Orbit>
 	// Load the inputs from file
 	let input = match std::fs::read_to_string("inputs/day6.txt")
 	{
-		Err(error) => panic!("Failed to open inputs: {}", error.description()),
+		Err(error) => panic!("Failed to open inputs: {}", error),
 		Ok(string) => string,
 	};
 	
@@ -64,7 +64,7 @@ fn load_inputs() -> Rc<Orbit>
 		for line in input.trim().lines()
 		{
 			let mut parts = line.split(')');
-			let ref mut children = map.entry(parts.next().unwrap()).or_insert(Vec::new());
+			let children = map.entry(parts.next().unwrap()).or_insert(Vec::new());
 			children.push(parts.next().unwrap());
 		}
 		map
@@ -111,9 +111,9 @@ fn dump(orbit :&Orbit, indent :usize) {
 	}
 	println!("{}", orbit.name);
 	
-	let ref children = *orbit.children.borrow();
+	let children = &*orbit.children.borrow();
 	for child in children {
-		dump(&child, indent + 1);
+		dump(child, indent + 1);
 	}
 }
 
@@ -127,14 +127,14 @@ fn count_orbits() -> usize
 	fn count_recursive(node :&Orbit, level :usize) -> usize {
 		let mut sum = 0;
 		
-		let ref children = *node.children.borrow();
+		let children = &*node.children.borrow();
 		for child in children {
 			sum += level;
-			sum += count_recursive(&child, level + 1);
+			sum += count_recursive(child, level + 1);
 		}
 		
 		return sum;
-	};
+	}
 	return count_recursive(&com, 1);
 }
 
@@ -146,14 +146,14 @@ fn get_transfers() -> usize
 	// Find a path from <name> to COM
 	fn find_node(node :&Orbit, name :&str, path :&mut Vec<String>) -> bool {
 		// Go through all the children looking for it
-		let ref children = *node.children.borrow();
+		let children = &*node.children.borrow();
 		for child in children {
 			if child.name == name {
 				// We found it
 				path.push(node.name.clone());
 				return true;
 			}
-			else if find_node(&child, name, path) {
+			else if find_node(child, name, path) {
 				// A child node was in the path so we must be too
 				path.push(node.name.clone());
 				return true;
@@ -161,7 +161,7 @@ fn get_transfers() -> usize
 		}
 		// The node wasn't found from us
 		return false;
-	};
+	}
 	
 	// First find a path from YOU to COM
 	let mut you = Vec::new();
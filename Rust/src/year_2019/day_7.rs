@@ -40,105 +40,200 @@
  */
 
 
-use std::error::Error;
-use super::intcode::{Interpreter, StepResult};
+use std::fmt;
+use std::sync::mpsc::{channel, Receiver};
+use super::combinatorics;
+use super::intcode::{Interpreter, IntcodeError, StepResult};
+use super::intcode::threaded::{Finished, Group, ThreadedMachine};
 
 
-struct Amps {
+// How the amplifiers are connected
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChainMode {
+	// Each amp feeds the next, and the last one is the result
+	Linear,
+	
+	// As linear, but the last amp also feeds back into the first
+	Feedback,
+}
+
+
+// Everything that went in and out of a single amp
+#[derive(Debug, Clone, Default)]
+struct StageTrace {
+	inputs : Vec<isize>,
+	outputs : Vec<isize>,
+}
+
+
+// The final signal, along with how it was produced so that there's something
+// to look at when it goes wrong
+#[derive(Debug)]
+struct ChainResult {
+	signal : Result<isize, ChainError>,
+	stages : Vec<StageTrace>,
+}
+
+
+// Reasons that a chain couldn't produce a signal
+#[derive(Debug)]
+enum ChainError {
+	// The program itself failed
+	Intcode { stage :usize, error :IntcodeError },
+	
+	// An amp halted but was still being sent signals
+	HaltedEarly { stage :usize },
+	
+	// An amp halted without producing any output for the next one
+	NoOutput { stage :usize },
+	
+	// An amp wanted more input than it was given, or all of them were
+	// waiting on each other
+	Starved { stage :usize },
+}
+
+
+impl fmt::Display for ChainError {
+	fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			ChainError::Intcode { stage, ref error } =>
+				write!(f, "Amp {} failed: {}", stage, error),
+			ChainError::HaltedEarly { stage } =>
+				write!(f, "Amp {} halted while signals were still being sent to it", stage),
+			ChainError::NoOutput { stage } =>
+				write!(f, "Amp {} didn't produce any output", stage),
+			ChainError::Starved { stage } =>
+				write!(f, "Amp {} wanted more input than it was given", stage),
+		}
+	}
+}
+
+
+// A set of amplifiers all running the same program
+struct AmplifierChain {
 	base : Interpreter,
 }
 
 
-impl Amps {
-	fn new() -> Amps {
+impl AmplifierChain {
+	fn new() -> AmplifierChain {
 		let input = load_input();
-		return Amps {
-			base : Interpreter::load(&input),
+		return AmplifierChain {
+			base : Interpreter::load(&input).unwrap(),
 		};
 	}
 	
-	fn run(self :&Amps, phase_a :usize, phase_b :usize, phase_c :usize, phase_d :usize, phase_e :usize) -> isize {
-		// Create the amps
-		let mut a = self.base.clone();
-		let mut b = self.base.clone();
-		let mut c = self.base.clone();
-		let mut d = self.base.clone();
-		let mut e = self.base.clone();
+	// Run a signal of 0 through an amp per phase, with each amp on its own
+	// thread so that it can produce as many outputs as it likes
+	fn run(self :&AmplifierChain, phases :&[isize], mode :ChainMode) -> ChainResult {
+		// With no amps, the signal goes straight to the thrusters
+		if phases.is_empty() {
+			return ChainResult { signal : Ok(0), stages : Vec::new() };
+		}
 		
-		let run_prog = |prog :&mut Interpreter, phase :usize, input :isize| {
-			// First input is the phase
-			prog.set_input(phase as isize);
-			let result = prog.run();
-			assert_eq!(result, StepResult::Input);
-			
-			// Second input is the input
-			prog.set_input(input);
-			let result = prog.run();
-			assert_eq!(result, StepResult::Break);
-			
-			return prog.get_outputs()[0];
-		};
+		// Create the amps, the first input to each is its phase. They're all
+		// in a group so that they notice if they end up waiting on each other.
+		let group = Group::new();
+		let amps :Vec<ThreadedMachine> = phases.iter()
+			.map(|&phase| {
+				let mut amp = ThreadedMachine::new(self.base.clone());
+				amp.join_group(&group);
+				let _ = amp.input().send(phase);
+				return amp;
+			})
+			.collect();
+		let _ = amps[0].input().send(0);
 		
-		// Run each in turn
-		let next_input = 0;
-		let next_input = run_prog(&mut a, phase_a, next_input);
-		let next_input = run_prog(&mut b, phase_b, next_input);
-		let next_input = run_prog(&mut c, phase_c, next_input);
-		let next_input = run_prog(&mut d, phase_d, next_input);
-		let next_input = run_prog(&mut e, phase_e, next_input);
-		return next_input;
-	}
-	
-	fn run_loop(self :&Amps, phase_a :usize, phase_b :usize, phase_c :usize, phase_d :usize, phase_e :usize) -> isize {
-		// Lambda to create an amp
-		let setup_amp = |phase :usize| {
-			let mut prog = self.base.clone();
-			
-			// First input is the phase
-			prog.set_input(phase as isize);
-			let result = prog.run();
-			assert_eq!(result, StepResult::Input);
-			
-			return prog;
+		// The last amp either goes to the thrusters or back round to the first
+		let (thrusters, signals) = channel();
+		let last_output = match mode {
+			ChainMode::Linear => thrusters,
+			ChainMode::Feedback => amps[0].input(),
 		};
 		
-		// Lambda to run the next part of the program
-		let run_prog = |prog :&mut Interpreter, input :&mut isize| {
-			// Input is the input
-			prog.set_input(*input);
-			let result = prog.run();
-			
-			// Save back the output
-			*input = prog.get_outputs()[0];
-			
-			// Return the result of the execution
-			return result;
-		};
+		// Start each amp feeding into the one after it
+		let mut handles = Vec::new();
+		let mut amps = amps.into_iter().peekable();
+		while let Some(amp) = amps.next() {
+			match amps.peek() {
+				Some(next) => handles.push(amp.spawn_into(next)),
+				None => {
+					handles.push(amp.spawn(last_output));
+					break;
+				},
+			}
+		}
+		let finished :Vec<Result<Finished, IntcodeError>> = handles.into_iter()
+			.map(|handle| handle.join())
+			.collect();
 		
-		// Create the amps
-		let mut a = setup_amp(phase_a);
-		let mut b = setup_amp(phase_b);
-		let mut c = setup_amp(phase_c);
-		let mut d = setup_amp(phase_d);
-		let mut e = setup_amp(phase_e);
+		// Each amp was sent its phase and then whatever came out of the one
+		// feeding it
+		let outputs :Vec<Vec<isize>> = finished.iter()
+			.map(|result| match *result {
+				Ok(ref amp) => amp.outputs.clone(),
+				Err(_) => Vec::new(),
+			})
+			.collect();
+		let stages = phases.iter().enumerate()
+			.map(|(stage, &phase)| {
+				let mut inputs = vec![phase];
+				let feeder = match (stage, mode) {
+					(0, ChainMode::Linear) => None,
+					(0, ChainMode::Feedback) => Some(phases.len() - 1),
+					_ => Some(stage - 1),
+				};
+				if stage == 0 {
+					inputs.push(0);
+				}
+				if let Some(feeder) = feeder {
+					inputs.extend(outputs[feeder].iter());
+				}
+				return StageTrace { inputs, outputs : outputs[stage].clone() };
+			})
+			.collect();
 		
-		// Run each in turn until it breaks
-		let mut next_input = 0;
-		loop {
-			// We can't add asserts about any of these since they all
-			// return break on the last pass
-			run_prog(&mut a, &mut next_input);
-			run_prog(&mut b, &mut next_input);
-			run_prog(&mut c, &mut next_input);
-			run_prog(&mut d, &mut next_input);
-			let result = run_prog(&mut e, &mut next_input);
-			
-			// If the final run produced a break then we're done
-			if result == StepResult::Break {
-				break;
+		return ChainResult {
+			signal : AmplifierChain::signal(finished, mode, signals),
+			stages,
+		};
+	}
+	
+	// Find what reached the thrusters, or the first amp to go wrong
+	fn signal(finished :Vec<Result<Finished, IntcodeError>>, mode :ChainMode, thrusters :Receiver<isize>)
+		-> Result<isize, ChainError>
+	{
+		let mut amps = Vec::new();
+		for (stage, result) in finished.into_iter().enumerate() {
+			match result {
+				Err(error) => return Err(ChainError::Intcode { stage, error }),
+				Ok(amp) => amps.push(amp),
 			}
 		}
-		return next_input;
+		
+		// Anything an amp didn't read was sent after it halted, apart from the
+		// final signal in feedback mode which goes back to the first amp
+		let mut signals :Vec<isize> = thrusters.try_iter().collect();
+		for (stage, amp) in amps.iter().enumerate() {
+			let unread :Vec<isize> = amp.inputs.try_iter().collect();
+			if stage == 0 && mode == ChainMode::Feedback && unread.len() <= 1 {
+				signals = unread;
+			}
+			else if !unread.is_empty() {
+				return Err(ChainError::HaltedEarly { stage });
+			}
+		}
+		
+		if let Some(stage) = amps.iter().position(|amp| matches!(amp.result, StepResult::Break) && amp.outputs.is_empty()) {
+			return Err(ChainError::NoOutput { stage });
+		}
+		if let Some(stage) = amps.iter().position(|amp| matches!(amp.result, StepResult::Input)) {
+			return Err(ChainError::Starved { stage });
+		}
+		return match signals.last() {
+			Some(&signal) => Ok(signal),
+			None => Err(ChainError::NoOutput { stage : amps.len() - 1 }),
+		};
 	}
 }
 
@@ -149,7 +244,7 @@ fn load_input() -> String
 	// Load the input from file
 	let input = match std::fs::read_to_string("inputs/day7.txt")
 	{
-		Err(error) => panic!("Failed to open input: {}", error.description()),
+		Err(error) => panic!("Failed to open input: {}", error),
 		Ok(string) => string,
 	};
 	
@@ -157,106 +252,218 @@ fn load_input() -> String
 }
 
 
-fn run_part1() -> isize
-{
-	// Load the program as provided into the amp
-	let amps = Amps::new();
-	
-	// Find the max
-	let mut max_output = 0;
-	let mut _max_phase = 0;
-	
-	// 5! = 120 combos, but we'll go through all of them to make the logic a
-	// little bit easier (ie so I don't have to add permutations)
-	for phase in 0..3125 {
-		// Peel the phases for each
-		let phase_a = (phase / 1) % 5;
-		let phase_b = (phase / 5) % 5;
-		let phase_c = (phase / 25) % 5;
-		let phase_d = (phase / 125) % 5;
-		let phase_e = (phase / 625) % 5;
-		
-		// Check that no digit appears twice
-		// Urgh, permutations might have been easier
-		let mut bins = vec![0;5];
-		bins[phase_a] += 1;
-		bins[phase_b] += 1;
-		bins[phase_c] += 1;
-		bins[phase_d] += 1;
-		bins[phase_e] += 1;
-		if !bins.iter().all(|&val| val == 1) {
-			continue;
+// The best signal found, and the phases that produced it
+#[derive(Debug, Clone, PartialEq)]
+struct PhaseSearch {
+	signal : isize,
+	phases : Vec<isize>,
+}
+
+
+// A set of phases that the amps couldn't run with, and what each amp got up
+// to before it went wrong
+#[derive(Debug)]
+struct SearchError {
+	phases : Vec<isize>,
+	error : ChainError,
+	stages : Vec<StageTrace>,
+}
+
+
+impl fmt::Display for SearchError {
+	fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+		write!(f, "Phases {:?} failed: {}", self.phases, self.error)?;
+		for (stage, trace) in self.stages.iter().enumerate() {
+			write!(f, "\n\tAmp {}: inputs {:?}, outputs {:?}", stage, trace.inputs, trace.outputs)?;
 		}
-		
-		// Run it
-		let output = amps.run(phase_a, phase_b, phase_c, phase_d, phase_e);
-		
-		// See if that was bigger
-		if output > max_output {
-			max_output = output;
-			
-			// Convert it back to a readable number
-			let mut max_phase = 0;
-			max_phase += phase_a * 10000;
-			max_phase += phase_b * 1000;
-			max_phase += phase_c * 100;
-			max_phase += phase_d * 10;
-			max_phase += phase_e * 1;
-			_max_phase = max_phase
+		return Ok(());
+	}
+}
+
+
+// Try each of the candidates in turn. Only a strictly bigger signal replaces
+// the current best, so the earliest candidate wins any ties.
+fn search_serial(amps :&AmplifierChain, candidates :&[Vec<isize>], mode :ChainMode)
+	-> Result<Option<PhaseSearch>, SearchError>
+{
+	let mut best :Option<PhaseSearch> = None;
+	for phases in candidates {
+		let result = amps.run(phases, mode);
+		let signal = match result.signal {
+			Err(error) => return Err(SearchError { phases : phases.clone(), error, stages : result.stages }),
+			Ok(signal) => signal,
+		};
+		if best.as_ref().is_none_or(|best| signal > best.signal) {
+			best = Some(PhaseSearch { signal, phases : phases.clone() });
 		}
 	}
-	
-	return max_output;
+	return Ok(best);
+}
+
+
+// Same as search_serial, but split across a worker per core. Each worker takes
+// a contiguous chunk and the results are merged back in chunk order, so ties
+// are broken exactly the same way as the serial search.
+fn search_parallel(amps :&AmplifierChain, candidates :&[Vec<isize>], mode :ChainMode)
+	-> Result<Option<PhaseSearch>, SearchError>
+{
+	let workers = std::thread::available_parallelism().map_or(1, |count| count.get());
+	return search_split(amps, candidates, mode, workers);
 }
 
 
-fn run_part2() -> isize
+// Search using the given number of workers
+fn search_split(amps :&AmplifierChain, candidates :&[Vec<isize>], mode :ChainMode, workers :usize)
+	-> Result<Option<PhaseSearch>, SearchError>
 {
-	// Load the program as provided into the amp
-	let amps = Amps::new();
+	let chunk_size = candidates.len().div_ceil(workers).max(1);
 	
-	// Find the max
-	let mut max_output = 0;
+	let results :Vec<_> = std::thread::scope(|scope| {
+		let handles :Vec<_> = candidates.chunks(chunk_size)
+			.map(|chunk| scope.spawn(move || search_serial(amps, chunk, mode)))
+			.collect();
+		return handles.into_iter()
+			.map(|handle| handle.join().expect("Phase search worker panicked"))
+			.collect();
+	});
 	
-	// 5! = 120 combos, but we'll go through all of them to make the logic a
-	// little bit easier (ie so I don't have to add permutations)
-	for phase in 0..3125 {
-		// Peel the phases for each
-		let phase_a = (phase / 1) % 5;
-		let phase_b = (phase / 5) % 5;
-		let phase_c = (phase / 25) % 5;
-		let phase_d = (phase / 125) % 5;
-		let phase_e = (phase / 625) % 5;
-		
-		// Check that no digit appears twice
-		// Urgh, permutations might have been easier
-		let mut bins = vec![0;5];
-		bins[phase_a] += 1;
-		bins[phase_b] += 1;
-		bins[phase_c] += 1;
-		bins[phase_d] += 1;
-		bins[phase_e] += 1;
-		if !bins.iter().all(|&val| val == 1) {
-			continue;
-		}
-		
-		// Run it, adding 5 to each phase as the numbers are now [5,9)
-		let output = amps.run_loop(phase_a + 5, phase_b + 5, phase_c + 5, phase_d + 5, phase_e + 5);
-		
-		// See if that was bigger
-		if output > max_output {
-			max_output = output;
+	let mut best :Option<PhaseSearch> = None;
+	for result in results {
+		if let Some(found) = result? {
+			if best.as_ref().is_none_or(|best| found.signal > best.signal) {
+				best = Some(found);
+			}
 		}
 	}
+	return Ok(best);
+}
+
+
+// Find the phases that give the biggest signal
+fn find_best(phases :&[isize], mode :ChainMode) -> PhaseSearch
+{
+	// Load the program as provided into the amps
+	let amps = AmplifierChain::new();
 	
-	return max_output;
+	// Try every ordering of the phases
+	let candidates :Vec<Vec<isize>> = combinatorics::permutations(phases).collect();
+	return match search_parallel(&amps, &candidates, mode) {
+		Err(error) => panic!("{}", error),
+		Ok(None) => panic!("No phases to try"),
+		Ok(Some(best)) => best,
+	};
+}
+
+
+fn run_part1() -> PhaseSearch
+{
+	return find_best(&[0, 1, 2, 3, 4], ChainMode::Linear);
+}
+
+
+fn run_part2() -> PhaseSearch
+{
+	// The phases are now [5,9]
+	return find_best(&[5, 6, 7, 8, 9], ChainMode::Feedback);
 }
 
 
 pub fn main()
 {
 	println!("Day7:");
-	println!("\tPart1 = {}", run_part1());
-	println!("\tPart2 = {}", run_part2());
+	let part1 = run_part1();
+	println!("\tPart1 = {} (phases {:?})", part1.signal, part1.phases);
+	let part2 = run_part2();
+	println!("\tPart2 = {} (phases {:?})", part2.signal, part2.phases);
 }
 
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn chain(source :&str) -> AmplifierChain {
+		return AmplifierChain { base : Interpreter::load(source).unwrap() };
+	}
+	
+	#[test]
+	fn halted_early() {
+		// Only reads its phase, so the signal sent to it is never read
+		let result = chain("3,0,104,5,99").run(&[0, 1], ChainMode::Linear);
+		assert!(matches!(result.signal, Err(ChainError::HaltedEarly { stage : 0 })), "{:?}", result.signal);
+		assert_eq!(result.stages[0].inputs, vec![0, 0]);
+		assert_eq!(result.stages[1].inputs, vec![1, 5]);
+	}
+	
+	#[test]
+	fn no_output() {
+		// Reads its phase and signal and then halts
+		let result = chain("3,0,3,0,99").run(&[0, 1], ChainMode::Linear);
+		assert!(matches!(result.signal, Err(ChainError::NoOutput { stage : 0 })), "{:?}", result.signal);
+		assert_eq!(result.stages[0].outputs, Vec::<isize>::new());
+	}
+	
+	#[test]
+	fn starved() {
+		// Wants a second signal before producing anything
+		let source = "3,0,3,0,3,0,4,0,99";
+		let result = chain(source).run(&[0, 1], ChainMode::Linear);
+		assert!(matches!(result.signal, Err(ChainError::Starved { stage : 0 })), "{:?}", result.signal);
+		
+		// In a loop every amp ends up waiting on the one before it, which has
+		// to be noticed every time rather than hanging
+		for _ in 0..50 {
+			let result = chain(source).run(&[5, 6, 7, 8, 9], ChainMode::Feedback);
+			assert!(matches!(result.signal, Err(ChainError::Starved { stage : 0 })), "{:?}", result.signal);
+		}
+	}
+	
+	// Search every ordering of the phases serially and split up in a few
+	// different ways, and check they all agree
+	fn search(source :&str, phases :&[isize], mode :ChainMode) -> PhaseSearch {
+		let amps = chain(source);
+		let candidates :Vec<Vec<isize>> = combinatorics::permutations(phases).collect();
+		let serial = search_serial(&amps, &candidates, mode).unwrap().unwrap();
+		assert_eq!(search_parallel(&amps, &candidates, mode).unwrap().as_ref(), Some(&serial));
+		for workers in [1, 2, 3, 7, 200] {
+			assert_eq!(search_split(&amps, &candidates, mode, workers).unwrap().as_ref(), Some(&serial),
+				"{} workers", workers);
+		}
+		return serial;
+	}
+	
+	#[test]
+	fn search_linear() {
+		let best = search("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0", &[0, 1, 2, 3, 4], ChainMode::Linear);
+		assert_eq!(best, PhaseSearch { signal : 43210, phases : vec![4, 3, 2, 1, 0] });
+	}
+	
+	#[test]
+	fn search_feedback() {
+		let source = "3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5";
+		let best = search(source, &[5, 6, 7, 8, 9], ChainMode::Feedback);
+		assert_eq!(best, PhaseSearch { signal : 139629729, phases : vec![9, 8, 7, 6, 5] });
+	}
+	
+	#[test]
+	fn search_tie() {
+		// Passes the signal straight through, so every ordering gives 0 and
+		// the first one has to win
+		let best = search("3,0,3,0,4,0,99", &[0, 1, 2, 3, 4], ChainMode::Linear);
+		assert_eq!(best, PhaseSearch { signal : 0, phases : vec![0, 1, 2, 3, 4] });
+	}
+	
+	#[test]
+	fn search_nothing() {
+		let amps = chain("3,0,3,0,4,0,99");
+		assert_eq!(search_serial(&amps, &[], ChainMode::Linear).unwrap(), None);
+		assert_eq!(search_parallel(&amps, &[], ChainMode::Linear).unwrap(), None);
+		assert_eq!(search_split(&amps, &[], ChainMode::Linear, 3).unwrap(), None);
+	}
+	
+	#[test]
+	fn intcode_error() {
+		let result = chain("3,0,3,0,4,-1,99").run(&[0, 1], ChainMode::Linear);
+		assert!(matches!(result.signal, Err(ChainError::Intcode { stage : 0, .. })), "{:?}", result.signal);
+	}
+}
@@ -30,9 +30,6 @@
  */
 
 
-use std::error::Error;
-
-
 struct ImageLayer {
 	data : Vec<usize>,
 }
@@ -88,7 +85,7 @@ fn load_input() -> String
 	// Load the input from file
 	let input = match std::fs::read_to_string("inputs/day8.txt")
 	{
-		Err(error) => panic!("Failed to open input: {}", error.description()),
+		Err(error) => panic!("Failed to open input: {}", error),
 		Ok(string) => string,
 	};
 	
@@ -107,7 +104,7 @@ fn run_part1() -> usize
 	let mut product = 0;
 	for layer in image.layers {
 		// Count the number of 0s, 1s, and 2s
-		let mut bins = vec![0; 3];
+		let mut bins = [0; 3];
 		for pixel in layer.data {
 			bins[pixel] += 1;
 		}
@@ -137,7 +134,7 @@ fn run_part2()
 	// Go through each layer and apply the colouring
 	for layer in image.layers {
 		for idx in 0..(25*6) {
-			let ref mut pixel = final_image.data[idx];
+			let pixel = &mut final_image.data[idx];
 			match pixel {
 				// We already have a final colour, so nothing to do
 				0 => {},
@@ -165,7 +162,7 @@ fn run_part2()
 				_ => panic!("Unexpected pixel colour: {}", pixel),
 			};
 		}
-		println!("");
+		println!();
 	}
 }
 
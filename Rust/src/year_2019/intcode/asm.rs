@@ -0,0 +1,340 @@
+/**
+ * Assembler for writing Intcode programs by hand.
+ *
+ * Each line holds at most one instruction or directive, optionally preceded by
+ * a label and followed by a comment:
+ *
+ *   loop:  in -> [rb+1]        ; read a value
+ *          jz [rb+1], #done
+ *          out [rb+1]
+ *          jnz #1, #loop
+ *   done:  hlt
+ *   value: .data 1, 2, done+1
+ *
+ * Operands are written as #imm for immediate, [addr] for position and [rb+n]
+ * (or [r+n]) for relative mode, so rb and r can't be used as labels. Values
+ * can be numbers, labels, or a label plus/minus a number. The write operand
+ * can be separated with either ',' or '->', so the output of the disassembler
+ * can be fed straight back in, including its "0012:" address prefixes and
+ * DATA lines.
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+use super::decode::{Instruction, Mode, Opcode};
+
+
+// A problem with the source, with the line it happened on (1-based)
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsmError {
+	pub line : usize,
+	pub message : String,
+}
+
+
+impl fmt::Display for AsmError {
+	fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+		return write!(f, "Line {}: {}", self.line, self.message);
+	}
+}
+
+
+impl std::error::Error for AsmError {}
+
+
+// A value that might need a label resolving before it's known
+#[derive(Debug)]
+enum Value {
+	Number(isize),
+	Label(String, isize),
+}
+
+
+// A single operand of an instruction
+#[derive(Debug)]
+struct Operand {
+	mode : Mode,
+	value : Value,
+}
+
+
+// Everything that ends up emitting words
+#[derive(Debug)]
+enum Item {
+	Instruction { line :usize, ins :Instruction, operands :Vec<Operand> },
+	Data { line :usize, values :Vec<Value> },
+}
+
+
+// Assemble source into the comma separated format that Interpreter::load takes
+pub fn assemble(source :&str) -> Result<String, AsmError> {
+	let words = assemble_words(source)?;
+	let words :Vec<String> = words.iter().map(|word| word.to_string()).collect();
+	return Ok(words.join(","));
+}
+
+
+// Assemble source into the raw memory words
+pub fn assemble_words(source :&str) -> Result<Vec<isize>, AsmError> {
+	let mut labels :HashMap<String, isize> = HashMap::new();
+	let mut items = Vec::new();
+	let mut addr = 0;
+	
+	// First pass parses everything and works out where the labels are
+	for (idx, text) in source.lines().enumerate() {
+		let line = idx + 1;
+		let error = |message :String| AsmError { line, message };
+		
+		// Drop any comment
+		let mut text = match text.find(';') {
+			Some(pos) => &text[..pos],
+			None => text,
+		}.trim();
+		
+		// Peel off any labels
+		while let Some(pos) = text.find(':') {
+			let name = text[..pos].trim();
+			if !name.is_empty() && name.chars().all(|c| c.is_ascii_digit()) {
+				// Address prefix from the disassembler, so check it lines up
+				if name.parse::<isize>() != Ok(addr) {
+					return Err(error(format!("Address {} doesn't match actual address {}", name, addr)));
+				}
+			} else if RELATIVE_NAMES.contains(&name) {
+				// These would always be read as the relative base in [..]
+				return Err(error(format!("'{}' is reserved for the relative base", name)));
+			} else if is_identifier(name) {
+				if labels.insert(name.to_string(), addr).is_some() {
+					return Err(error(format!("Label '{}' defined twice", name)));
+				}
+			} else {
+				return Err(error(format!("Invalid label '{}'", name)));
+			}
+			text = text[pos + 1..].trim();
+		}
+		if text.is_empty() {
+			continue;
+		}
+		
+		// Split off the mnemonic from the operands
+		let (name, rest) = match text.find(char::is_whitespace) {
+			Some(pos) => (&text[..pos], text[pos..].trim()),
+			None => (text, ""),
+		};
+		
+		// Instructions that only write have nothing before the arrow
+		let rest = match rest.strip_prefix("->") {
+			Some(rest) => rest.trim(),
+			None => rest,
+		};
+		let operands :Vec<&str> = if rest.is_empty() {
+			Vec::new()
+		} else {
+			rest.split(',').flat_map(|op| op.split("->")).map(str::trim).collect()
+		};
+		
+		// Data is just a list of values
+		if name.eq_ignore_ascii_case(".data") || name.eq_ignore_ascii_case("data") {
+			let values = operands.iter()
+				.map(|op| parse_value(op).ok_or_else(|| error(format!("Invalid value '{}'", op))))
+				.collect::<Result<Vec<_>, _>>()?;
+			addr += values.len() as isize;
+			items.push(Item::Data { line, values });
+			continue;
+		}
+		
+		// Otherwise it should be an instruction
+		let opcode = match Opcode::from_mnemonic(name) {
+			Some(opcode) => opcode,
+			None => return Err(error(format!("Unknown mnemonic '{}'", name))),
+		};
+		if operands.len() != opcode.operand_count() {
+			return Err(error(format!("{} takes {} operands but {} were given",
+				opcode.mnemonic(), opcode.operand_count(), operands.len())));
+		}
+		let operands = operands.iter()
+			.map(|op| parse_operand(op).ok_or_else(|| error(format!("Invalid operand '{}'", op))))
+			.collect::<Result<Vec<_>, _>>()?;
+		
+		// Build the instruction word and let the decoder check it's valid
+		let mut modes = [Mode::Position; 3];
+		for (mode, operand) in modes.iter_mut().zip(operands.iter()) {
+			*mode = operand.mode;
+		}
+		let ins = Instruction { opcode, modes };
+		if let Err(err) = Instruction::decode(addr as usize, ins.encode()) {
+			return Err(error(err.to_string()));
+		}
+		
+		addr += ins.size() as isize;
+		items.push(Item::Instruction { line, ins, operands });
+	}
+	
+	// Second pass resolves the labels and emits the words
+	let mut words = Vec::new();
+	for item in items.iter() {
+		match *item {
+			Item::Instruction { line, ref ins, ref operands } => {
+				words.push(ins.encode());
+				for operand in operands.iter() {
+					words.push(resolve(&operand.value, &labels, line)?);
+				}
+			},
+			Item::Data { line, ref values } => {
+				for value in values.iter() {
+					words.push(resolve(value, &labels, line)?);
+				}
+			},
+		}
+	}
+	
+	return Ok(words);
+}
+
+
+// Names for the relative base inside brackets, which can't be used as labels
+const RELATIVE_NAMES :[&str; 2] = ["rb", "r"];
+
+
+// Labels have to look like identifiers so they can't be confused with numbers
+fn is_identifier(name :&str) -> bool {
+	let mut chars = name.chars();
+	return match chars.next() {
+		Some(c) if c.is_ascii_alphabetic() || c == '_' =>
+			chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+		_ => false,
+	};
+}
+
+
+// Parse a number, label, or label with an offset
+fn parse_value(text :&str) -> Option<Value> {
+	let text = text.trim();
+	if let Ok(val) = text.parse() {
+		return Some(Value::Number(val));
+	}
+	
+	// Look for an offset after the label
+	let (name, offset) = match text.rfind(['+', '-']) {
+		Some(pos) => {
+			let offset = text[pos..].replace(" ", "");
+			let offset = offset.trim_start_matches('+').parse().ok()?;
+			(text[..pos].trim(), offset)
+		},
+		None => (text, 0),
+	};
+	if !is_identifier(name) {
+		return None;
+	}
+	return Some(Value::Label(name.to_string(), offset));
+}
+
+
+// Parse an operand along with its mode
+fn parse_operand(text :&str) -> Option<Operand> {
+	// Immediate
+	if let Some(text) = text.strip_prefix('#') {
+		let value = parse_value(text)?;
+		return Some(Operand { mode : Mode::Immediate, value });
+	}
+	
+	// Everything else has to be in brackets
+	if !text.starts_with('[') || !text.ends_with(']') {
+		return None;
+	}
+	let inner = text[1..text.len() - 1].trim();
+	
+	// Relative, which can be written as either rb or r
+	for prefix in RELATIVE_NAMES.iter() {
+		if let Some(offset) = inner.strip_prefix(prefix) {
+			let offset = offset.trim();
+			if offset.is_empty() {
+				return Some(Operand { mode : Mode::Relative, value : Value::Number(0) });
+			}
+			if offset.starts_with('+') || offset.starts_with('-') {
+				let offset = offset.replace(" ", "");
+				let value = parse_value(offset.trim_start_matches('+'))?;
+				return Some(Operand { mode : Mode::Relative, value });
+			}
+		}
+	}
+	
+	// Position
+	let value = parse_value(inner)?;
+	return Some(Operand { mode : Mode::Position, value });
+}
+
+
+// Turn a value into a number now that all the labels are known
+fn resolve(value :&Value, labels :&HashMap<String, isize>, line :usize) -> Result<isize, AsmError> {
+	return match *value {
+		Value::Number(val) => Ok(val),
+		Value::Label(ref name, offset) => match labels.get(name) {
+			Some(addr) => Ok(addr + offset),
+			None => Err(AsmError { line, message : format!("Unknown label '{}'", name) }),
+		},
+	};
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::disasm;
+	
+	// Assemble the source, disassemble the result, and check that assembling
+	// the listing gives back the same words
+	fn round_trip(source :&str) -> Vec<isize> {
+		let words = assemble_words(source).unwrap();
+		let listing = disasm::listing(&words, 0);
+		assert_eq!(assemble_words(&listing), Ok(words.clone()), "{}", listing);
+		return words;
+	}
+	
+	#[test]
+	fn labels_and_data() {
+		let source = "\
+			start:  in -> [rb+1]        ; read a value\n\
+			        jz [rb+1], #done\n\
+			        add [value], [rb-2] -> [value+1]\n\
+			        out [rb+1]\n\
+			        jnz #1, #start\n\
+			done:   hlt\n\
+			value:  .data 1, -2, done+1, start-1\n";
+		let words = round_trip(source);
+		assert_eq!(words, vec![203, 1, 1206, 1, 14, 2001, 15, -2, 16, 204, 1, 1105, 1, 0, 99, 1, -2, 15, -1]);
+	}
+	
+	#[test]
+	fn relative_offsets() {
+		let words = round_trip("arb #-5\nmul [r], #-3 -> [rb-4]\nout [r+0]\nhlt\n");
+		assert_eq!(words, vec![109, -5, 21202, 0, -3, -4, 204, 0, 99]);
+	}
+	
+	#[test]
+	fn non_canonical_words() {
+		// These decode, but with mode digits that HLT and OUT don't use
+		let words = round_trip("hlt\nvals: .data 1099, 10099, 11104, 7\n");
+		assert_eq!(words, vec![99, 1099, 10099, 11104, 7]);
+		assert_eq!(disasm::listing(&words, 0), "\
+			0000: HLT\n\
+			0001: DATA 1099\n\
+			0002: DATA 10099\n\
+			0003: DATA 11104\n\
+			0004: DATA 7\n");
+	}
+	
+	#[test]
+	fn listing() {
+		let words = assemble_words("in -> [rb+1]\nadd #3, [7] -> [rb-1]\nhlt\n").unwrap();
+		assert_eq!(disasm::listing(&words, 0), "\
+			0000: IN -> [r+1]\n\
+			0002: ADD #3, [7] -> [r-1]\n\
+			0006: HLT\n");
+	}
+	
+	#[test]
+	fn relative_names_are_reserved() {
+		assert_eq!(assemble_words("rb: hlt").unwrap_err().line, 1);
+		assert_eq!(assemble_words("hlt\nr: hlt").unwrap_err().line, 2);
+	}
+}
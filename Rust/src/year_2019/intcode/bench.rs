@@ -0,0 +1,85 @@
+/**
+ * Timing how fast programs run, for comparing different interpreter setups
+ * against each other on the same program.
+ *
+ * Every setup is the current interpreter, so turning the decode cache off
+ * gives one that decodes every instruction like it did before the cache was
+ * added, but it isn't the old code. The checks for the cache are still there,
+ * so it's only a rough stand-in for how fast things used to be.
+ */
+
+use std::time::{Duration, Instant};
+use super::{Interpreter, IntcodeError, StepResult};
+use super::memory::Memory;
+
+
+// How long to keep running a program for each measurement
+pub const DEFAULT_DURATION :Duration = Duration::from_secs(1);
+
+
+// The totals for running a program over and over
+#[derive(Debug, Clone, Copy)]
+pub struct Timing {
+	pub runs : usize,
+	pub steps : usize,
+	pub elapsed : Duration,
+}
+
+
+impl Timing {
+	pub fn steps_per_sec(self :&Timing) -> f64 {
+		return self.steps as f64 / self.elapsed.as_secs_f64();
+	}
+}
+
+
+// Run a fresh copy of the program over and over until the time is up. The
+// program is passed to setup once, and then each copy of it is fed the inputs
+// and run until it halts or wants more input. Only the running is timed, not
+// making the copies.
+pub fn time<M, F>(program :&Interpreter<M>, inputs :&[isize], duration :Duration, setup :F)
+	-> Result<Timing, IntcodeError>
+	where M :Memory, F :FnOnce(&mut Interpreter<M>)
+{
+	let mut base = program.clone();
+	setup(&mut base);
+	
+	let mut runs = 0;
+	let mut steps = 0;
+	let mut elapsed = Duration::ZERO;
+	while runs == 0 || elapsed < duration {
+		let mut machine = base.clone();
+		machine.extend_inputs(inputs.iter().cloned());
+		let start = Instant::now();
+		loop {
+			steps += 1;
+			match machine.step()? {
+				StepResult::Continue | StepResult::Output(_) => {},
+				_ => break,
+			}
+		}
+		elapsed += start.elapsed();
+		runs += 1;
+	}
+	
+	return Ok(Timing {
+		runs,
+		steps,
+		elapsed,
+	});
+}
+
+
+// Write out a comparison of some timings, with the first as the baseline
+pub fn report(timings :&[(&str, Timing)]) -> String {
+	let mut output = String::from("Speedups are relative to the first row, which is the current interpreter\n\
+		with the decode cache off rather than the interpreter from before the cache\n\n");
+	let mut baseline = None;
+	for &(name, ref timing) in timings.iter() {
+		let rate = timing.steps_per_sec();
+		let speedup = rate / *baseline.get_or_insert(rate);
+		output += &format!("{:<24} {:>8} runs {:>12} steps {:>8.2}M steps/s  {:.2}x\n",
+			name, timing.runs, timing.steps, rate / 1e6, speedup);
+	}
+	return output;
+}
@@ -0,0 +1,58 @@
+/**
+ * Cache of decoded instruction words, so that code which runs over and over
+ * again only has its opcode and modes worked out once.
+ *
+ * Only the instruction word is cached, the operands are still read from
+ * memory every time. That means the only write that can make an entry stale
+ * is one to the instruction word itself, so self-modifying code keeps working
+ * as long as every write calls invalidate().
+ *
+ * Every word is decoded up front, whether it's code or not, since an entry
+ * only depends on the word at that address. That way copies of a program that
+ * hasn't been run yet (like each of the day 7 amps) start off fully decoded.
+ * Only the image is covered, anything outside of it is decoded every time so
+ * that running code at a far away address doesn't need a huge cache.
+ */
+
+use super::decode::Instruction;
+
+
+#[derive(Debug, Clone)]
+pub struct DecodeCache {
+	entries : Vec<Option<Instruction>>,
+}
+
+
+impl DecodeCache {
+	// Decode everything in memory that looks like an instruction
+	pub fn new(mem :&[isize]) -> DecodeCache {
+		return DecodeCache {
+			entries : mem.iter()
+				.enumerate()
+				.map(|(pc, &ins)| Instruction::decode(pc, ins).ok())
+				.collect(),
+		};
+	}
+	
+	// Get the decoded instruction at pc, if it's been seen before
+	pub fn get(self :&DecodeCache, pc :usize) -> Option<Instruction> {
+		return match self.entries.get(pc) {
+			Some(&entry) => entry,
+			None => None,
+		};
+	}
+	
+	// Remember the decoded instruction at pc, if it's in the image
+	pub fn insert(self :&mut DecodeCache, pc :usize, decoded :Instruction) {
+		if let Some(entry) = self.entries.get_mut(pc) {
+			*entry = Some(decoded);
+		}
+	}
+	
+	// Forget anything decoded from the given address, since it's changed
+	pub fn invalidate(self :&mut DecodeCache, addr :usize) {
+		if let Some(entry) = self.entries.get_mut(addr) {
+			*entry = None;
+		}
+	}
+}
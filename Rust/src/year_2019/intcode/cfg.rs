@@ -0,0 +1,290 @@
+/**
+ * Static control flow analysis, for getting a feel for what a program does
+ * without running it.
+ *
+ * Decoding starts at the entry point and follows every path it can see:
+ * falling through to the next instruction, and jumps with immediate targets.
+ * Anything it can't follow is reported as an issue rather than guessed at:
+ *
+ *   - jumps through memory, since the target is only known at runtime
+ *   - writes into words that were decoded as code, since the program might
+ *     not look the same by the time it runs
+ *   - words that can't be decoded, or jumps to addresses that can't exist
+ *
+ * Writes in relative mode can't be resolved without knowing the relative
+ * base, so they're not checked.
+ *
+ * The result can be written out as Graphviz DOT, eg:
+ *
+ *   aoc cfg inputs/day5.txt | dot -Tsvg > day5.svg
+ */
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use super::decode::{Instruction, Mode, Opcode};
+use super::disasm::{self, Line};
+
+
+// How control gets from one block to another
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeKind {
+	// Straight line flow into the next block
+	Next,
+	
+	// A conditional jump that was taken
+	Taken,
+	
+	// A conditional jump that wasn't taken
+	NotTaken,
+	
+	// A jump that always happens
+	Jump,
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edge {
+	pub target : usize,
+	pub kind : EdgeKind,
+}
+
+
+// Anything that the analysis couldn't follow
+#[derive(Debug, Clone, PartialEq)]
+pub enum Issue {
+	// A jump whose target is read from memory
+	IndirectJump { addr :usize },
+	
+	// A jump to an address that can't exist
+	BadTarget { addr :usize, target :isize },
+	
+	// A write into a word that was decoded as code
+	SelfModifying { addr :usize, target :usize },
+	
+	// Control reaches a word that isn't a valid instruction
+	Undecodable { addr :usize },
+}
+
+
+impl fmt::Display for Issue {
+	fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Issue::IndirectJump { addr } =>
+				write!(f, "Indirect jump at {:04}", addr),
+			Issue::BadTarget { addr, target } =>
+				write!(f, "Jump to invalid address {} at {:04}", target, addr),
+			Issue::SelfModifying { addr, target } =>
+				write!(f, "Write into code at {:04} by {:04}", target, addr),
+			Issue::Undecodable { addr } =>
+				write!(f, "Invalid instruction at {:04}", addr),
+		}
+	}
+}
+
+
+// A straight run of instructions that's only ever entered at the top
+#[derive(Debug, Clone)]
+pub struct Block {
+	pub start : usize,
+	pub lines : Vec<Line>,
+	pub successors : Vec<Edge>,
+}
+
+
+// The blocks reachable from the entry point, keyed by their start address
+#[derive(Debug, Clone)]
+pub struct Cfg {
+	pub entry : usize,
+	pub blocks : BTreeMap<usize, Block>,
+	pub issues : Vec<Issue>,
+}
+
+
+// Decode the instruction at addr, making sure all of its operands are there
+fn decode_at(mem :&[isize], addr :usize) -> Option<Instruction> {
+	let decoded = Instruction::decode(addr, *mem.get(addr)?).ok()?;
+	if addr + decoded.size() > mem.len() {
+		return None;
+	}
+	return Some(decoded);
+}
+
+
+// Does this instruction always end a block
+fn ends_block(decoded :Instruction) -> bool {
+	return matches!(decoded.opcode, Opcode::JumpNonZero | Opcode::JumpZero | Opcode::Halt);
+}
+
+
+// Work out where control can go after the instruction at addr
+fn flow(mem :&[isize], addr :usize, decoded :Instruction, issues :&mut Vec<Issue>) -> Vec<Edge> {
+	let next = addr + decoded.size();
+	let taken_when_nonzero = match decoded.opcode {
+		Opcode::Halt => return Vec::new(),
+		Opcode::JumpNonZero => true,
+		Opcode::JumpZero => false,
+		_ => return vec![Edge { target : next, kind : EdgeKind::Next }],
+	};
+	
+	// An immediate condition means it either always or never jumps
+	let condition = mem[addr + 1];
+	let always = match decoded.modes[0] {
+		Mode::Immediate => Some((condition != 0) == taken_when_nonzero),
+		_ => None,
+	};
+	let mut edges = Vec::new();
+	if always != Some(true) {
+		let kind = if always.is_none() { EdgeKind::NotTaken } else { EdgeKind::Next };
+		edges.push(Edge { target : next, kind });
+	}
+	if always == Some(false) {
+		return edges;
+	}
+	
+	// Only immediate targets can be followed
+	let target = mem[addr + 2];
+	if decoded.modes[1] != Mode::Immediate {
+		issues.push(Issue::IndirectJump { addr });
+	} else if target < 0 || target as usize >= mem.len() {
+		issues.push(Issue::BadTarget { addr, target });
+	} else {
+		let kind = if always.is_none() { EdgeKind::Taken } else { EdgeKind::Jump };
+		edges.push(Edge { target : target as usize, kind });
+	}
+	return edges;
+}
+
+
+// Build the control flow graph for everything reachable from entry
+pub fn analyse(mem :&[isize], entry :usize) -> Cfg {
+	let mut issues = Vec::new();
+	
+	// Find every reachable instruction, and where control can go from each
+	let mut instructions :BTreeMap<usize, Instruction> = BTreeMap::new();
+	let mut edges :BTreeMap<usize, Vec<Edge>> = BTreeMap::new();
+	let mut undecodable = BTreeSet::new();
+	let mut leaders = BTreeSet::new();
+	leaders.insert(entry);
+	let mut pending = vec![entry];
+	while let Some(addr) = pending.pop() {
+		if instructions.contains_key(&addr) || undecodable.contains(&addr) {
+			continue;
+		}
+		let decoded = match decode_at(mem, addr) {
+			Some(decoded) => decoded,
+			None => {
+				issues.push(Issue::Undecodable { addr });
+				undecodable.insert(addr);
+				continue;
+			},
+		};
+		
+		// Anything after the end of a block starts a new one
+		let successors = flow(mem, addr, decoded, &mut issues);
+		for edge in successors.iter() {
+			if ends_block(decoded) {
+				leaders.insert(edge.target);
+			}
+			pending.push(edge.target);
+		}
+		instructions.insert(addr, decoded);
+		edges.insert(addr, successors);
+	}
+	
+	// Look for writes into anything that was decoded as code
+	let code :BTreeSet<usize> = instructions.iter()
+		.flat_map(|(&addr, decoded)| addr..addr + decoded.size())
+		.collect();
+	for (&addr, decoded) in instructions.iter() {
+		if let Some(idx) = decoded.opcode.write_operand() {
+			let target = mem[addr + 1 + idx];
+			if decoded.modes[idx] == Mode::Position && target >= 0 && code.contains(&(target as usize)) {
+				issues.push(Issue::SelfModifying { addr, target : target as usize });
+			}
+		}
+	}
+	
+	// Split the instructions up into blocks
+	let mut blocks = BTreeMap::new();
+	for &start in leaders.iter().filter(|addr| instructions.contains_key(addr)) {
+		let mut lines = Vec::new();
+		let mut addr = start;
+		let successors = loop {
+			if let Some(line) = disasm::disassemble(mem, addr).next() {
+				lines.push(line);
+			}
+			
+			let decoded = instructions[&addr];
+			let next = addr + decoded.size();
+			if ends_block(decoded) || leaders.contains(&next) || !instructions.contains_key(&next) {
+				break edges[&addr].clone();
+			}
+			addr = next;
+		};
+		blocks.insert(start, Block { start, lines, successors });
+	}
+	
+	issues.sort_by_key(|issue| match *issue {
+		Issue::IndirectJump { addr } => addr,
+		Issue::BadTarget { addr, .. } => addr,
+		Issue::SelfModifying { addr, .. } => addr,
+		Issue::Undecodable { addr } => addr,
+	});
+	return Cfg {
+		entry,
+		blocks,
+		issues,
+	};
+}
+
+
+impl Cfg {
+	// Write the graph out in Graphviz DOT format
+	pub fn to_dot(self :&Cfg) -> String {
+		let mut output = String::new();
+		output += "digraph intcode {\n";
+		output += "\tnode [shape=box, fontname=\"monospace\"];\n";
+		for issue in self.issues.iter() {
+			output += &format!("\t// {}\n", issue);
+		}
+		output += &format!("\tentry [shape=point];\n\tentry -> b{};\n", self.entry);
+		
+		// Blocks with something odd going on get coloured in
+		let mut missing = BTreeSet::new();
+		for block in self.blocks.values() {
+			let end = block.lines.last().map_or(block.start, |line| line.addr + line.size);
+			let flagged = self.issues.iter().any(|issue| match *issue {
+				Issue::IndirectJump { addr } | Issue::BadTarget { addr, .. } |
+				Issue::SelfModifying { addr, .. } => addr >= block.start && addr < end,
+				Issue::Undecodable { .. } => false,
+			});
+			
+			let mut label = String::new();
+			for line in block.lines.iter() {
+				label += &format!("{}\\l", line);
+			}
+			let colour = if flagged { ", color=red" } else { "" };
+			output += &format!("\tb{} [label=\"{}\"{}];\n", block.start, label, colour);
+			
+			for edge in block.successors.iter() {
+				if !self.blocks.contains_key(&edge.target) {
+					missing.insert(edge.target);
+				}
+				let style = match edge.kind {
+					EdgeKind::Next | EdgeKind::Jump => "",
+					EdgeKind::Taken => " [label=\"taken\"]",
+					EdgeKind::NotTaken => " [label=\"not taken\", style=dashed]",
+				};
+				output += &format!("\tb{} -> b{}{};\n", block.start, edge.target, style);
+			}
+		}
+		
+		// Anything that control reaches but isn't code
+		for addr in missing {
+			output += &format!("\tb{} [label=\"{:04}: ???\", color=red];\n", addr, addr);
+		}
+		
+		output += "}\n";
+		return output;
+	}
+}
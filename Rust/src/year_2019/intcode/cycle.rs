@@ -0,0 +1,92 @@
+/**
+ * Spotting programs that are stuck in a loop they can never get out of.
+ *
+ * Execution is deterministic, so if the machine is ever in exactly the same
+ * state twice (same pc, relative base, memory and pending inputs) without
+ * being given anything new, it's going to go round the same way forever.
+ * Outputs don't affect what the program does next, so they're left out.
+ *
+ * Any loop has to jump backwards, so the state is only checked there. Rather
+ * than remembering every state seen, only one is kept and it's swapped for
+ * the current state after 1, 2, 4, 8... checks (Brent's algorithm), which
+ * finds any cycle while only using enough memory for a single copy.
+ *
+ * States are compared by hash first to keep the common case cheap, and then
+ * in full so that a collision can't cause a false alarm.
+ *
+ * Anything done to the machine from the outside (like giving it more input)
+ * breaks the chain, so a detector should only be used for a single run.
+ */
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use super::memory::Memory;
+
+
+// Everything that decides what the machine does next
+#[derive(Debug, Clone)]
+struct State<M :Memory> {
+	pc : usize,
+	relative_base : isize,
+	mem : M,
+	inputs : VecDeque<isize>,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct CycleDetector<M :Memory> {
+	// The state that we're looking to see again, along with its hash
+	saved : Option<(u64, State<M>)>,
+	
+	// How many checks since the state was saved, and how many to wait before
+	// saving a new one
+	checks : usize,
+	limit : usize,
+}
+
+
+impl<M :Memory> CycleDetector<M> {
+	pub fn new() -> CycleDetector<M> {
+		return CycleDetector {
+			saved : None,
+			checks : 0,
+			limit : 1,
+		};
+	}
+	
+	// Check the state of the machine after a backward jump, returning true if
+	// it's been seen before
+	pub fn check(self :&mut CycleDetector<M>, pc :usize, relative_base :isize, mem :&M, inputs :&VecDeque<isize>) -> bool {
+		let mut hasher = DefaultHasher::new();
+		pc.hash(&mut hasher);
+		relative_base.hash(&mut hasher);
+		mem.hash_words(&mut hasher);
+		inputs.hash(&mut hasher);
+		let hash = hasher.finish();
+		
+		// Compare against the saved state, only doing it properly if the hash
+		// says it could be the same
+		if let Some((saved_hash, ref saved)) = self.saved {
+			if saved_hash == hash && saved.pc == pc && saved.relative_base == relative_base
+				&& saved.mem == *mem && saved.inputs == *inputs
+			{
+				return true;
+			}
+		}
+		
+		// Move the saved state along if it's been long enough
+		self.checks += 1;
+		if self.saved.is_none() || self.checks >= self.limit {
+			self.saved = Some((hash, State {
+				pc,
+				relative_base,
+				mem : mem.clone(),
+				inputs : inputs.clone(),
+			}));
+			self.checks = 0;
+			self.limit *= 2;
+		}
+		return false;
+	}
+}
@@ -0,0 +1,421 @@
+/**
+ * Interactive debugger, built on top of Interpreter::step.
+ *
+ * Commands are read a line at a time so that it can be driven from a script
+ * as well as a terminal:
+ *
+ *   step [n]            execute n instructions (default 1)
+ *   continue            run until a breakpoint, watchpoint, input or halt
+ *   back [n]            undo the last n instructions (default 1)
+ *   backto <pc>         undo instructions until pc is reached again
+ *   break <pc>          stop when pc reaches the address
+ *   delete <pc>         remove a breakpoint
+ *   watch <addr>        stop when the value at the address changes
+ *   unwatch <addr>      remove a watchpoint
+ *   input <val>...      queue up input values
+ *   regs                print pc, relative base and pending inputs
+ *   disasm [n]          print n instructions from pc (default 5), along with
+ *                       a few before it
+ *   mem <addr> [n]      print n words of memory (default 1)
+ *   trace <n>           record the last n instructions (0 to turn off)
+ *   trace               print the recorded instructions
+ *   save <path>         save a snapshot of the machine to a file
+ *   restore <path>      replace the machine with a snapshot from a file
+ *   quit                stop debugging
+ */
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, BufRead, Write};
+use super::{cfg, disasm, Interpreter, StepResult};
+use super::decode::Instruction;
+use super::disasm::Line;
+
+
+// How many instructions can be stepped back over
+const UNDO_CAPACITY :usize = 1_000_000;
+
+// How many instructions to show before pc when disassembling
+const DISASM_BEFORE :usize = 3;
+
+
+// Why execution stopped
+#[derive(Debug, PartialEq)]
+enum Stop {
+	Running,
+	Breakpoint,
+	Watchpoint,
+	Input,
+	Halted,
+	Error,
+}
+
+
+pub struct Debugger {
+	machine : Interpreter,
+	breakpoints : BTreeSet<usize>,
+	watchpoints : BTreeMap<usize, isize>,
+	halted : bool,
+}
+
+
+impl Debugger {
+	pub fn new(mut machine :Interpreter) -> Debugger {
+		machine.enable_undo_log(UNDO_CAPACITY);
+		return Debugger {
+			machine,
+			breakpoints : BTreeSet::new(),
+			watchpoints : BTreeMap::new(),
+			halted : false,
+		};
+	}
+	
+	// Read and execute commands until told to quit or the input runs out
+	pub fn run<R, W>(self :&mut Debugger, input :R, output :&mut W) -> io::Result<()>
+		where R :BufRead, W :Write
+	{
+		for line in input.lines() {
+			let line = line?;
+			let args :Vec<&str> = line.split_whitespace().collect();
+			if args.is_empty() {
+				continue;
+			}
+			if !self.execute(&args, output)? {
+				break;
+			}
+		}
+		return Ok(());
+	}
+	
+	// Execute a single command, returning false if it was a request to quit
+	fn execute<W :Write>(self :&mut Debugger, args :&[&str], output :&mut W) -> io::Result<bool> {
+		// Snapshots are the only commands that take a path
+		match (args[0], args.get(1)) {
+			("save", Some(path)) => {
+				if let Err(error) = std::fs::write(path, self.machine.save()) {
+					writeln!(output, "Failed to save {}: {}", path, error)?;
+				}
+				return Ok(true);
+			},
+			
+			("restore", Some(path)) => {
+				match std::fs::read_to_string(path) {
+					Err(error) => writeln!(output, "Failed to open {}: {}", path, error)?,
+					Ok(snapshot) => match Interpreter::restore(&snapshot) {
+						Err(error) => writeln!(output, "Failed to restore {}: {}", path, error)?,
+						Ok(machine) => {
+							self.machine = machine;
+							self.machine.enable_undo_log(UNDO_CAPACITY);
+							self.resync();
+							self.print_current(output)?;
+						},
+					},
+				}
+				return Ok(true);
+			},
+			
+			("save", None) | ("restore", None) => {
+				writeln!(output, "Usage: {} <path>", args[0])?;
+				return Ok(true);
+			},
+			
+			_ => {},
+		}
+		
+		// Parse the numerical arguments up front
+		let mut values = Vec::new();
+		for arg in args[1..].iter() {
+			match arg.parse::<isize>() {
+				Ok(val) => values.push(val),
+				Err(_) => {
+					writeln!(output, "Invalid number: {}", arg)?;
+					return Ok(true);
+				},
+			}
+		}
+		let address = |idx :usize| values.get(idx).filter(|&&val| val >= 0).map(|&val| val as usize);
+		
+		match args[0] {
+			"s" | "step" => {
+				let count = address(0).unwrap_or(1);
+				for _ in 0..count {
+					if self.single_step(output)? != Stop::Running {
+						break;
+					}
+				}
+				self.print_current(output)?;
+			},
+			
+			"c" | "continue" => {
+				// Always take one step so that continuing from a breakpoint
+				// doesn't just stop on it again
+				let mut stop = self.single_step(output)?;
+				while stop == Stop::Running {
+					if self.breakpoints.contains(&self.machine.pc()) {
+						writeln!(output, "Breakpoint at {:04}", self.machine.pc())?;
+						stop = Stop::Breakpoint;
+					} else {
+						stop = self.single_step(output)?;
+					}
+				}
+				self.print_current(output)?;
+			},
+			
+			"back" => {
+				let count = address(0).unwrap_or(1);
+				for _ in 0..count {
+					if !self.machine.step_back() {
+						writeln!(output, "Nothing left to undo")?;
+						break;
+					}
+				}
+				self.resync();
+				self.print_current(output)?;
+			},
+			
+			"backto" => match address(0) {
+				Some(pc) => {
+					if !self.machine.run_back_to(pc) {
+						writeln!(output, "Nothing left to undo")?;
+					}
+					self.resync();
+					self.print_current(output)?;
+				},
+				None => writeln!(output, "Usage: backto <pc>")?,
+			},
+			
+			"b" | "break" => match address(0) {
+				Some(pc) => {
+					self.breakpoints.insert(pc);
+				},
+				None => writeln!(output, "Usage: break <pc>")?,
+			},
+			
+			"delete" => match address(0) {
+				Some(pc) => {
+					self.breakpoints.remove(&pc);
+				},
+				None => writeln!(output, "Usage: delete <pc>")?,
+			},
+			
+			"w" | "watch" => match address(0) {
+				Some(addr) => {
+					self.watchpoints.insert(addr, self.machine.get(addr));
+				},
+				None => writeln!(output, "Usage: watch <addr>")?,
+			},
+			
+			"unwatch" => match address(0) {
+				Some(addr) => {
+					self.watchpoints.remove(&addr);
+				},
+				None => writeln!(output, "Usage: unwatch <addr>")?,
+			},
+			
+			"i" | "input" => self.machine.extend_inputs(values),
+			
+			"r" | "regs" => {
+				let inputs :Vec<String> = self.machine.pending_inputs().iter()
+					.map(|val| val.to_string())
+					.collect();
+				writeln!(output, "pc={} rb={} inputs=[{}]",
+					self.machine.pc(), self.machine.relative_base(), inputs.join(", "))?;
+			},
+			
+			"d" | "disasm" => {
+				let count = address(0).unwrap_or(5);
+				let mem = self.machine.memory();
+				let pc = self.machine.pc();
+				let lines = lines_before(mem, pc, DISASM_BEFORE).into_iter()
+					.chain(disasm::disassemble(mem, pc).take(count));
+				for line in lines {
+					let marker = if line.addr == pc { ">" } else { " " };
+					writeln!(output, "{} {}", marker, line)?;
+				}
+			},
+			
+			"x" | "mem" => match address(0) {
+				Some(addr) => {
+					let count = address(1).unwrap_or(1);
+					for idx in addr..addr + count {
+						writeln!(output, "[{}] = {}", idx, self.machine.get(idx))?;
+					}
+				},
+				None => writeln!(output, "Usage: mem <addr> [count]")?,
+			},
+			
+			"t" | "trace" => match address(0) {
+				Some(0) => self.machine.disable_trace(),
+				Some(capacity) => self.machine.enable_trace(capacity),
+				None => match self.machine.trace() {
+					Some(trace) => {
+						for entry in trace.entries() {
+							writeln!(output, "{}", entry)?;
+						}
+					},
+					None => writeln!(output, "Tracing is not enabled")?,
+				},
+			},
+			
+			"q" | "quit" => return Ok(false),
+			
+			_ => writeln!(output, "Unknown command: {}", args[0])?,
+		}
+		
+		return Ok(true);
+	}
+	
+	// Execute one instruction and report anything interesting that happened
+	fn single_step<W :Write>(self :&mut Debugger, output :&mut W) -> io::Result<Stop> {
+		if self.halted {
+			writeln!(output, "Program has halted")?;
+			return Ok(Stop::Halted);
+		}
+		
+		let mut stop = match self.machine.step() {
+			Ok(StepResult::Continue) => Stop::Running,
+			Ok(StepResult::Output(val)) => {
+				writeln!(output, "Output: {}", val)?;
+				Stop::Running
+			},
+			Ok(StepResult::Input) => {
+				writeln!(output, "Waiting for input")?;
+				Stop::Input
+			},
+			Ok(StepResult::Break) => {
+				writeln!(output, "Program has halted")?;
+				self.halted = true;
+				Stop::Halted
+			},
+			Ok(result) => {
+				writeln!(output, "Unexpected step result: {:?}", result)?;
+				Stop::Error
+			},
+			Err(error) => {
+				writeln!(output, "Error: {}", error)?;
+				Stop::Error
+			},
+		};
+		
+		// See if any of the watched values changed
+		for (&addr, old) in self.watchpoints.iter_mut() {
+			let new = self.machine.get(addr);
+			if new != *old {
+				writeln!(output, "Watchpoint [{}]: {} -> {}", addr, old, new)?;
+				*old = new;
+				if stop == Stop::Running {
+					stop = Stop::Watchpoint;
+				}
+			}
+		}
+		
+		return Ok(stop);
+	}
+	
+	// Catch up with a machine that's been changed other than by stepping it,
+	// without treating it as hitting any watchpoints
+	fn resync(self :&mut Debugger) {
+		self.halted = false;
+		for (&addr, val) in self.watchpoints.iter_mut() {
+			*val = self.machine.get(addr);
+		}
+	}
+	
+	// Print the instruction that's about to execute
+	fn print_current<W :Write>(self :&Debugger, output :&mut W) -> io::Result<()> {
+		if self.halted {
+			return Ok(());
+		}
+		if let Some(line) = disasm::disassemble(self.machine.memory(), self.machine.pc()).next() {
+			writeln!(output, "{}", line)?;
+		}
+		return Ok(());
+	}
+}
+
+
+// The instructions leading up to pc. Memory can't be read backwards, so this
+// looks for somewhere a little before pc that disassembles cleanly up to it,
+// trying the instruction starts that the control flow knows about first and
+// then any address. Starting as far back as possible gives the most context.
+fn lines_before(mem :&[isize], pc :usize, count :usize) -> Vec<Line> {
+	let window = pc.saturating_sub(count * 4)..pc;
+	let known = cfg::analyse(mem, 0).blocks.values()
+		.flat_map(|block| block.lines.iter().map(|line| line.addr))
+		.filter(|addr| window.contains(addr))
+		.collect::<BTreeSet<usize>>();
+	
+	for start in known.into_iter().chain(window.clone()) {
+		let lines :Vec<Line> = disasm::disassemble(mem, start)
+			.take_while(|line| line.addr < pc)
+			.collect();
+		let lands = lines.last().is_some_and(|line| line.addr + line.size == pc);
+		let decodes = lines.iter().all(|line| Instruction::decode(line.addr, mem[line.addr]).is_ok());
+		if lands && decodes {
+			let skip = lines.len().saturating_sub(count);
+			return lines.into_iter().skip(skip).collect();
+		}
+	}
+	return Vec::new();
+}
+
+
+#[cfg(test)]
+mod tests {
+	use std::io::Cursor;
+	use super::*;
+	
+	// Reads numbers until it gets a 0, echoing each one, then outputs the count
+	const ECHO :&str = "109,100,203,1,1206,1,16,204,1,1001,19,1,19,1105,1,2,4,19,99,0";
+	
+	fn debug(script :&str) -> String {
+		let mut debugger = Debugger::new(Interpreter::load(ECHO).unwrap());
+		let mut output = Vec::new();
+		debugger.run(Cursor::new(script), &mut output).unwrap();
+		return String::from_utf8(output).unwrap();
+	}
+	
+	#[test]
+	fn scripted_session() {
+		let script = "input 5 7\nstep 4\ndisasm 2\nback 2\nregs\nbreak 16\ncontinue\n\
+			input 0\ncontinue\ncontinue\nbogus\nquit\nstep\n";
+		let expected = "\
+			Output: 5\n\
+			0009: ADD [19], #1 -> [19]\n  \
+			0002: IN -> [r+1]\n  \
+			0004: JZ [r+1], #16\n  \
+			0007: OUT [r+1]\n\
+			> 0009: ADD [19], #1 -> [19]\n  \
+			0013: JNZ #1, #2\n\
+			0004: JZ [r+1], #16\n\
+			pc=4 rb=100 inputs=[7]\n\
+			Output: 5\n\
+			Output: 7\n\
+			Waiting for input\n\
+			0002: IN -> [r+1]\n\
+			Breakpoint at 0016\n\
+			0016: OUT [19]\n\
+			Output: 2\n\
+			Program has halted\n\
+			Unknown command: bogus\n";
+		assert_eq!(debug(script), expected);
+	}
+	
+	#[test]
+	fn disasm_from_the_start() {
+		// Nothing comes before the first instruction
+		assert_eq!(debug("disasm 1\n"), "> 0000: ARB #100\n");
+	}
+	
+	#[test]
+	fn huge_trace_capacity() {
+		// Only what's actually recorded should take up any memory
+		assert_eq!(debug("trace 99999999999999\nstep 1\ntrace\n"), "0002: IN -> [r+1]\n0000: ARB 100\n");
+	}
+	
+	#[test]
+	fn watchpoints() {
+		let output = debug("watch 19\ninput 3 0\ncontinue\nmem 19\n");
+		assert!(output.contains("Watchpoint [19]: 0 -> 1\n"), "{}", output);
+		assert!(output.ends_with("[19] = 1\n"), "{}", output);
+	}
+}
@@ -0,0 +1,203 @@
+/**
+ * Instruction decoding, shared by the interpreter and the tooling that looks
+ * at programs without running them.
+ */
+
+use super::IntcodeError;
+
+
+// The operations that an instruction can perform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Opcode {
+	Add,
+	Mul,
+	Input,
+	Output,
+	JumpNonZero,
+	JumpZero,
+	LessThan,
+	Equals,
+	AdjustBase,
+	Halt,
+}
+
+
+// How an operand should be interpreted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+	Position,
+	Immediate,
+	Relative,
+}
+
+
+// A fully decoded instruction word
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instruction {
+	pub opcode : Opcode,
+	pub modes : [Mode; 3],
+}
+
+
+impl Opcode {
+	// Every opcode, in numerical order
+	pub const ALL :[Opcode; 10] = [
+		Opcode::Add,
+		Opcode::Mul,
+		Opcode::Input,
+		Opcode::Output,
+		Opcode::JumpNonZero,
+		Opcode::JumpZero,
+		Opcode::LessThan,
+		Opcode::Equals,
+		Opcode::AdjustBase,
+		Opcode::Halt,
+	];
+	
+	// Look up the opcode from the lower two digits of an instruction
+	pub fn from_value(val :isize) -> Option<Opcode> {
+		return match val {
+			1 => Some(Opcode::Add),
+			2 => Some(Opcode::Mul),
+			3 => Some(Opcode::Input),
+			4 => Some(Opcode::Output),
+			5 => Some(Opcode::JumpNonZero),
+			6 => Some(Opcode::JumpZero),
+			7 => Some(Opcode::LessThan),
+			8 => Some(Opcode::Equals),
+			9 => Some(Opcode::AdjustBase),
+			99 => Some(Opcode::Halt),
+			_ => None,
+		};
+	}
+	
+	// The value that gets encoded into an instruction
+	pub fn value(self) -> isize {
+		return match self {
+			Opcode::Add => 1,
+			Opcode::Mul => 2,
+			Opcode::Input => 3,
+			Opcode::Output => 4,
+			Opcode::JumpNonZero => 5,
+			Opcode::JumpZero => 6,
+			Opcode::LessThan => 7,
+			Opcode::Equals => 8,
+			Opcode::AdjustBase => 9,
+			Opcode::Halt => 99,
+		};
+	}
+	
+	// Look up the opcode from its mnemonic, ignoring case
+	pub fn from_mnemonic(name :&str) -> Option<Opcode> {
+		return Opcode::ALL.iter()
+			.find(|opcode| opcode.mnemonic().eq_ignore_ascii_case(name))
+			.cloned();
+	}
+	
+	// Short name used when printing programs
+	pub fn mnemonic(self) -> &'static str {
+		return match self {
+			Opcode::Add => "ADD",
+			Opcode::Mul => "MUL",
+			Opcode::Input => "IN",
+			Opcode::Output => "OUT",
+			Opcode::JumpNonZero => "JNZ",
+			Opcode::JumpZero => "JZ",
+			Opcode::LessThan => "LT",
+			Opcode::Equals => "EQ",
+			Opcode::AdjustBase => "ARB",
+			Opcode::Halt => "HLT",
+		};
+	}
+	
+	// How many operands follow the instruction
+	pub fn operand_count(self) -> usize {
+		return match self {
+			Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals => 3,
+			Opcode::JumpNonZero | Opcode::JumpZero => 2,
+			Opcode::Input | Opcode::Output | Opcode::AdjustBase => 1,
+			Opcode::Halt => 0,
+		};
+	}
+	
+	// Which operand (if any) is the address that gets written to
+	pub fn write_operand(self) -> Option<usize> {
+		return match self {
+			Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals => Some(2),
+			Opcode::Input => Some(0),
+			_ => None,
+		};
+	}
+}
+
+
+impl Mode {
+	// Look up a parameter mode from its digit
+	pub fn from_value(val :isize) -> Option<Mode> {
+		return match val {
+			0 => Some(Mode::Position),
+			1 => Some(Mode::Immediate),
+			2 => Some(Mode::Relative),
+			_ => None,
+		};
+	}
+	
+	// The digit that gets encoded into an instruction
+	pub fn value(self) -> isize {
+		return match self {
+			Mode::Position => 0,
+			Mode::Immediate => 1,
+			Mode::Relative => 2,
+		};
+	}
+}
+
+
+impl Instruction {
+	// Decode an instruction word, pc is only used for reporting errors
+	pub fn decode(pc :usize, ins :isize) -> Result<Instruction, IntcodeError> {
+		// The opcode lives in the lower two digits
+		let opcode = match Opcode::from_value(ins % 100) {
+			Some(opcode) => opcode,
+			None => return Err(IntcodeError::BadOpcode { pc, ins }),
+		};
+		
+		// Peel away the parameter modes, missing modes are 0 so only the
+		// operands that are actually used need to be checked
+		let mut modes = [Mode::Position; 3];
+		let mut divisor = 100;
+		for slot in modes.iter_mut().take(opcode.operand_count()) {
+			let mode = (ins / divisor) % 10;
+			*slot = match Mode::from_value(mode) {
+				Some(mode) => mode,
+				None => return Err(IntcodeError::BadMode { pc, ins, mode }),
+			};
+			divisor *= 10;
+		}
+		
+		// Writes can't be to an immediate
+		if let Some(idx) = opcode.write_operand() {
+			if modes[idx] == Mode::Immediate {
+				return Err(IntcodeError::InvalidWriteMode { pc, ins });
+			}
+		}
+		
+		return Ok(Instruction { opcode, modes });
+	}
+	
+	// Encode back into an instruction word
+	pub fn encode(self :&Instruction) -> isize {
+		let mut ins = self.opcode.value();
+		let mut multiplier = 100;
+		for mode in self.modes.iter() {
+			ins += mode.value() * multiplier;
+			multiplier *= 10;
+		}
+		return ins;
+	}
+	
+	// Total number of words taken up by the instruction and its operands
+	pub fn size(self :&Instruction) -> usize {
+		return 1 + self.opcode.operand_count();
+	}
+}
@@ -0,0 +1,223 @@
+/**
+ * Pluggable input/output for Intcode programs.
+ *
+ * A Machine pairs an Interpreter with a device, and feeds the program from
+ * the device whenever it asks for input, and hands every output to it.
+ */
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Stdin, Stdout, Write};
+use super::{Interpreter, IntcodeError, StepResult};
+
+
+// Something a program can talk to
+pub trait IoDevice {
+	// Get the next input, or None if there isn't one available yet
+	fn read(&mut self) -> Option<isize>;
+	
+	// Accept an output from the program
+	fn write(&mut self, val :isize);
+}
+
+
+// An interpreter connected up to a device
+pub struct Machine<D :IoDevice> {
+	pub interpreter : Interpreter,
+	pub device : D,
+}
+
+
+impl<D :IoDevice> Machine<D> {
+	pub fn new(interpreter :Interpreter, device :D) -> Machine<D> {
+		return Machine {
+			interpreter,
+			device,
+		};
+	}
+	
+	// Executes the program until it breaks, or until it wants input that
+	// the device doesn't have
+	pub fn run(self :&mut Machine<D>) -> Result<StepResult, IntcodeError> {
+		loop {
+			match self.interpreter.run_until_output()? {
+				StepResult::Output(val) => self.device.write(val),
+				StepResult::Input => match self.device.read() {
+					Some(val) => self.interpreter.push_input(val),
+					None => return Ok(StepResult::Input),
+				},
+				result => return Ok(result),
+			}
+		}
+	}
+}
+
+
+// Device that reads from a queue of inputs and collects the outputs
+#[derive(Debug, Clone, Default)]
+pub struct QueueDevice {
+	pub inputs : VecDeque<isize>,
+	pub outputs : Vec<isize>,
+}
+
+
+impl QueueDevice {
+	pub fn new<I>(inputs :I) -> QueueDevice
+		where I :IntoIterator<Item = isize>
+	{
+		return QueueDevice {
+			inputs : inputs.into_iter().collect(),
+			outputs : Vec::new(),
+		};
+	}
+}
+
+
+impl IoDevice for QueueDevice {
+	fn read(&mut self) -> Option<isize> {
+		return self.inputs.pop_front();
+	}
+	
+	fn write(&mut self, val :isize) {
+		self.outputs.push(val);
+	}
+}
+
+
+// Device that calls out to closures
+pub struct FnDevice<R, W>
+	where R :FnMut() -> Option<isize>, W :FnMut(isize)
+{
+	read : R,
+	write : W,
+}
+
+
+impl<R, W> FnDevice<R, W>
+	where R :FnMut() -> Option<isize>, W :FnMut(isize)
+{
+	pub fn new(read :R, write :W) -> FnDevice<R, W> {
+		return FnDevice {
+			read,
+			write,
+		};
+	}
+}
+
+
+impl<R, W> IoDevice for FnDevice<R, W>
+	where R :FnMut() -> Option<isize>, W :FnMut(isize)
+{
+	fn read(&mut self) -> Option<isize> {
+		return (self.read)();
+	}
+	
+	fn write(&mut self, val :isize) {
+		(self.write)(val);
+	}
+}
+
+
+// Device that reads and writes one number per line of text
+pub struct LineDevice<R :BufRead, W :Write> {
+	reader : R,
+	writer : W,
+}
+
+
+impl<R :BufRead, W :Write> LineDevice<R, W> {
+	pub fn new(reader :R, writer :W) -> LineDevice<R, W> {
+		return LineDevice {
+			reader,
+			writer,
+		};
+	}
+}
+
+
+impl LineDevice<BufReader<Stdin>, Stdout> {
+	// Talk to the terminal
+	pub fn stdio() -> LineDevice<BufReader<Stdin>, Stdout> {
+		return LineDevice::new(BufReader::new(io::stdin()), io::stdout());
+	}
+}
+
+
+impl<R :BufRead, W :Write> IoDevice for LineDevice<R, W> {
+	fn read(&mut self) -> Option<isize> {
+		// Keep going until we get something that looks like a number, giving
+		// up at the end of the input
+		loop {
+			let mut line = String::new();
+			match self.reader.read_line(&mut line) {
+				Ok(0) | Err(_) => return None,
+				Ok(_) => {},
+			}
+			match line.trim().parse() {
+				Ok(val) => return Some(val),
+				Err(_) => {
+					let _ = writeln!(self.writer, "Not a number: {}", line.trim());
+				},
+			}
+		}
+	}
+	
+	fn write(&mut self, val :isize) {
+		let _ = writeln!(self.writer, "{}", val);
+	}
+}
+
+
+// Device for programs that talk in ASCII, where input is read a line at a
+// time and output is printed as text. Anything outside of ASCII can't be
+// text, so it's written out as a number on its own line instead.
+pub struct AsciiDevice<R :BufRead, W :Write> {
+	reader : R,
+	writer : W,
+	pending : VecDeque<isize>,
+}
+
+
+impl<R :BufRead, W :Write> AsciiDevice<R, W> {
+	pub fn new(reader :R, writer :W) -> AsciiDevice<R, W> {
+		return AsciiDevice {
+			reader,
+			writer,
+			pending : VecDeque::new(),
+		};
+	}
+}
+
+
+impl AsciiDevice<BufReader<Stdin>, Stdout> {
+	// Talk to the terminal
+	pub fn stdio() -> AsciiDevice<BufReader<Stdin>, Stdout> {
+		return AsciiDevice::new(BufReader::new(io::stdin()), io::stdout());
+	}
+}
+
+
+impl<R :BufRead, W :Write> IoDevice for AsciiDevice<R, W> {
+	fn read(&mut self) -> Option<isize> {
+		// Grab the next line if we've run out, making sure it's terminated
+		// the same way every time
+		if self.pending.is_empty() {
+			let mut line = String::new();
+			match self.reader.read_line(&mut line) {
+				Ok(0) | Err(_) => return None,
+				Ok(_) => {},
+			}
+			let line = line.trim_end_matches(['\r', '\n']);
+			self.pending.extend(line.bytes().map(|byte| byte as isize));
+			self.pending.push_back('\n' as isize);
+		}
+		return self.pending.pop_front();
+	}
+	
+	fn write(&mut self, val :isize) {
+		let _ = if (0..128).contains(&val) {
+			write!(self.writer, "{}", val as u8 as char)
+		} else {
+			writeln!(self.writer, "{}", val)
+		};
+	}
+}
@@ -0,0 +1,124 @@
+/**
+ * Disassembler for turning Intcode memory back into a readable listing.
+ */
+
+use std::fmt;
+use super::decode::{Instruction, Mode};
+
+
+// A single line of the listing
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+	pub addr : usize,
+	pub size : usize,
+	pub text : String,
+}
+
+
+impl fmt::Display for Line {
+	fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+		return write!(f, "{:04}: {}", self.addr, self.text);
+	}
+}
+
+
+// Iterator that walks memory one instruction at a time
+pub struct Disassembly<'a> {
+	mem : &'a [isize],
+	addr : usize,
+}
+
+
+impl<'a> Iterator for Disassembly<'a> {
+	type Item = Line;
+	
+	fn next(&mut self) -> Option<Line> {
+		if self.addr >= self.mem.len() {
+			return None;
+		}
+		
+		// Anything that can't be decoded is treated as a single word of data
+		let addr = self.addr;
+		let line = match format_instruction(self.mem, addr) {
+			Some((text, size)) => Line { addr, size, text },
+			None => Line { addr, size : 1, text : format!("DATA {}", self.mem[addr]) },
+		};
+		
+		self.addr += line.size;
+		return Some(line);
+	}
+}
+
+
+// Disassemble memory starting from the given address
+pub fn disassemble<'a>(mem :&'a [isize], start :usize) -> Disassembly<'a> {
+	return Disassembly {
+		mem,
+		addr : start,
+	};
+}
+
+
+// Disassemble memory starting from the given address into a single string
+pub fn listing(mem :&[isize], start :usize) -> String {
+	let mut output = String::new();
+	for line in disassemble(mem, start) {
+		output += &format!("{}\n", line);
+	}
+	return output;
+}
+
+
+// Format an operand based on its mode
+pub fn format_operand(val :isize, mode :Mode) -> String {
+	return match mode {
+		Mode::Position => format!("[{}]", val),
+		Mode::Immediate => format!("#{}", val),
+		Mode::Relative if val < 0 => format!("[r{}]", val),
+		Mode::Relative => format!("[r+{}]", val),
+	};
+}
+
+
+// Try to decode and format the instruction at addr, returning the text and
+// the number of words that it takes up
+fn format_instruction(mem :&[isize], addr :usize) -> Option<(String, usize)> {
+	let decoded = match Instruction::decode(addr, mem[addr]) {
+		Ok(decoded) => decoded,
+		Err(_) => return None,
+	};
+	
+	// Mode digits past the operands that are used (like 1099) still decode,
+	// but they'd be lost when the listing is assembled again, so call it data
+	if decoded.encode() != mem[addr] {
+		return None;
+	}
+	
+	// If the operands run off the end then it's probably not code
+	let size = decoded.size();
+	if addr + size > mem.len() {
+		return None;
+	}
+	
+	// Format each operand
+	let operands :Vec<String> = (0..decoded.opcode.operand_count())
+		.map(|idx| format_operand(mem[addr + 1 + idx], decoded.modes[idx]))
+		.collect();
+	
+	// The write operand is always last, so split it off to make it stand out
+	let mut text = decoded.opcode.mnemonic().to_string();
+	let (sources, dest) = match decoded.opcode.write_operand() {
+		Some(idx) => (&operands[..idx], Some(&operands[idx])),
+		None => (&operands[..], None),
+	};
+	if !sources.is_empty() {
+		text += " ";
+		text += &sources.join(", ");
+	}
+	if let Some(dest) = dest {
+		text += " -> ";
+		text += dest;
+	}
+	
+	return Some((text, size));
+}
@@ -0,0 +1,51 @@
+/**
+ * Errors that can be produced when loading or running an Intcode program.
+ */
+
+use std::fmt;
+
+
+// Everything that can go wrong with a program
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntcodeError {
+	// A value in the program text couldn't be parsed as an integer
+	Parse { index :usize, token :String },
+	
+	// The opcode part of an instruction isn't one we know about
+	BadOpcode { pc :usize, ins :isize },
+	
+	// One of the parameter modes isn't one we know about
+	BadMode { pc :usize, ins :isize, mode :isize },
+	
+	// A write parameter was given in immediate mode
+	InvalidWriteMode { pc :usize, ins :isize },
+	
+	// An operand resolved to an address that can't exist
+	AddressOutOfRange { pc :usize, ins :isize, addr :isize },
+	
+	// The result of some arithmetic didn't fit in a word
+	Overflow { pc :usize, ins :isize },
+}
+
+
+impl fmt::Display for IntcodeError {
+	fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			IntcodeError::Parse { index, ref token } =>
+				write!(f, "Failed to parse value {:?} at index: {}", token, index),
+			IntcodeError::BadOpcode { pc, ins } =>
+				write!(f, "Unknown ins: {} at pc: {}", ins, pc),
+			IntcodeError::BadMode { pc, ins, mode } =>
+				write!(f, "Unknown parameter mode: {} for ins: {} at pc: {}", mode, ins, pc),
+			IntcodeError::InvalidWriteMode { pc, ins } =>
+				write!(f, "Write in immediate mode for ins: {} at pc: {}", ins, pc),
+			IntcodeError::AddressOutOfRange { pc, ins, addr } =>
+				write!(f, "Address out of range: {} for ins: {} at pc: {}", addr, ins, pc),
+			IntcodeError::Overflow { pc, ins } =>
+				write!(f, "Arithmetic overflow for ins: {} at pc: {}", ins, pc),
+		}
+	}
+}
+
+
+impl std::error::Error for IntcodeError {}
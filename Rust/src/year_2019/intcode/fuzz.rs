@@ -0,0 +1,755 @@
+/**
+ * Differential fuzzing of the different ways of running a program.
+ *
+ * Random programs are run on every engine and anything they disagree on is a
+ * bug in one of them. The engines are:
+ *
+ *   step       Interpreter::step, decoding every instruction from scratch
+ *   cached     the same but with the decode cache
+ *   sparse     the decode cache with SparseMemory
+ *   transpiled the Rust source from transpile(), built with rustc
+ *
+ * The first three are run side by side a step at a time, comparing every
+ * StepResult along with the final memory and outputs. The transpiled engine
+ * only has a run() so just the final state is compared, and it has to be
+ * compiled, so all of the programs are put into a single source file that
+ * gets built once. Programs that modify their own code are skipped for it,
+ * since stopping with NotTranspiled is exactly what it's meant to do.
+ *
+ * Programs are built from snippets rather than random words so that they
+ * always stop: loops have their own counter that nothing else writes to, and
+ * any other jump only goes forwards. Code is followed by a data area that all
+ * of the other reads and writes go to, with the relative base starting at the
+ * beginning of it. The exception is a far area well past the end of the image,
+ * which some snippets move the relative base over to, so that memory has to
+ * grow to reach it and SparseMemory has to create pages for it. The image and
+ * the far area are what get compared at the end.
+ *
+ * When the engines disagree, the program is shrunk by taking snippets out for
+ * as long as they still disagree. Every smaller version from a round is
+ * checked at once, so the transpiled engine is only built once per round.
+ */
+
+use super::{transpile, Interpreter, IntcodeError, StepResult};
+use super::decode::Opcode;
+use super::memory::{DenseMemory, Memory, SparseMemory, PAGE_SIZE};
+
+
+// Words in the data area, and in the far area
+const DATA_SIZE :usize = 16;
+
+// Where the far area starts, far enough out to be past any image and
+// straddling a page boundary
+const FAR_START :usize = 100 * PAGE_SIZE - DATA_SIZE / 2;
+
+// How deep loops can be nested
+const MAX_DEPTH :usize = 2;
+
+// Generated programs always stop well before this, it's only a backstop
+const MAX_STEPS :usize = 100_000;
+
+// How many programs to build into each transpiled binary
+const BATCH_SIZE :usize = 200;
+
+// The engines that are run in-process, the first is the one to compare with
+const ENGINES :[&str; 3] = ["step", "cached", "sparse"];
+
+
+// Small and repeatable source of random numbers (xorshift64*)
+pub struct Rng {
+	state : u64,
+}
+
+
+impl Rng {
+	pub fn new(seed :u64) -> Rng {
+		// The state must never be zero or it stays that way
+		let state = seed ^ 0x9e37_79b9_7f4a_7c15;
+		return Rng {
+			state : if state == 0 { 1 } else { state },
+		};
+	}
+	
+	fn next(self :&mut Rng) -> u64 {
+		self.state ^= self.state >> 12;
+		self.state ^= self.state << 25;
+		self.state ^= self.state >> 27;
+		return self.state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+	}
+	
+	// A number from 0 up to but not including n
+	fn below(self :&mut Rng, n :usize) -> usize {
+		return (self.next() % n as u64) as usize;
+	}
+	
+	// A number from lo to hi inclusive
+	fn range(self :&mut Rng, lo :isize, hi :isize) -> isize {
+		return lo + self.below((hi - lo + 1) as usize) as isize;
+	}
+}
+
+
+// Where an operand points, resolved to a mode and value when laid out
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+	Immediate(isize),
+	
+	// A word in the data area, by position or relative to the base
+	Data(usize),
+	Relative(usize),
+}
+
+
+// A piece of a program that can be added or taken away as a whole
+#[derive(Debug, Clone, PartialEq)]
+enum Snippet {
+	// Any instruction that doesn't jump or move the relative base
+	Op { opcode :Opcode, operands :Vec<Operand> },
+	
+	// Move the relative base for the ops inside, and then back again
+	Shifted { shift :isize, body :Vec<Snippet> },
+	
+	// Move the relative base to the far area for the ops inside, so that
+	// their relative operands go there instead of the data area
+	Far { body :Vec<Snippet> },
+	
+	// Jump over the next few snippets depending on the condition
+	Skip { opcode :Opcode, cond :Operand, skip :usize },
+	
+	// Run the body a fixed number of times
+	Loop { count :isize, body :Vec<Snippet> },
+	
+	// Change the opcode of one of the arithmetic or comparison ops, picked by
+	// its position in the program
+	Patch { target :usize, opcode :Opcode },
+}
+
+
+// A generated program along with the inputs to give it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+	snippets : Vec<Snippet>,
+	data : Vec<isize>,
+	pub inputs : Vec<isize>,
+}
+
+
+// The ops that calculate something into memory, which can be patched
+fn is_binary(opcode :Opcode) -> bool {
+	return matches!(opcode, Opcode::Add | Opcode::Mul | Opcode::LessThan | Opcode::Equals);
+}
+
+
+fn read_operand(rng :&mut Rng) -> Operand {
+	return match rng.below(3) {
+		0 => Operand::Immediate(rng.range(-10, 10)),
+		1 => Operand::Data(rng.below(DATA_SIZE)),
+		_ => Operand::Relative(rng.below(DATA_SIZE)),
+	};
+}
+
+
+fn write_operand(rng :&mut Rng) -> Operand {
+	return match rng.below(2) {
+		0 => Operand::Data(rng.below(DATA_SIZE)),
+		_ => Operand::Relative(rng.below(DATA_SIZE)),
+	};
+}
+
+
+fn random_op(rng :&mut Rng) -> Snippet {
+	let opcode = match rng.below(8) {
+		0 | 1 => Opcode::Add,
+		2 => Opcode::Mul,
+		3 => Opcode::LessThan,
+		4 => Opcode::Equals,
+		5 => Opcode::Input,
+		_ => Opcode::Output,
+	};
+	let operands = match opcode {
+		Opcode::Input => vec![write_operand(rng)],
+		Opcode::Output => vec![read_operand(rng)],
+		_ => vec![read_operand(rng), read_operand(rng), write_operand(rng)],
+	};
+	return Snippet::Op { opcode, operands };
+}
+
+
+fn random_block(rng :&mut Rng, depth :usize, len :usize) -> Vec<Snippet> {
+	let mut block = Vec::new();
+	for _ in 0..len {
+		block.push(match rng.below(20) {
+			0..=1 => Snippet::Shifted {
+				shift : rng.range(-5, 5),
+				body : (0..rng.range(1, 3)).map(|_| random_op(rng)).collect(),
+			},
+			2..=4 => Snippet::Skip {
+				opcode : if rng.below(2) == 0 { Opcode::JumpZero } else { Opcode::JumpNonZero },
+				cond : read_operand(rng),
+				skip : rng.range(1, 3) as usize,
+			},
+			5..=6 if depth < MAX_DEPTH => {
+				let count = rng.range(1, 4);
+				let len = rng.range(1, 5) as usize;
+				Snippet::Loop { count, body : random_block(rng, depth + 1, len) }
+			},
+			7 => Snippet::Patch {
+				target : rng.below(100),
+				opcode : [Opcode::Add, Opcode::Mul, Opcode::LessThan, Opcode::Equals][rng.below(4)],
+			},
+			8..=9 => Snippet::Far {
+				body : (0..rng.range(1, 3)).map(|_| random_op(rng)).collect(),
+			},
+			_ => random_op(rng),
+		});
+	}
+	return block;
+}
+
+
+impl Case {
+	pub fn random(rng :&mut Rng) -> Case {
+		let len = rng.range(1, 20) as usize;
+		return Case {
+			snippets : random_block(rng, 0, len),
+			data : (0..DATA_SIZE).map(|_| rng.range(-10, 10)).collect(),
+			inputs : (0..rng.range(0, 6)).map(|_| rng.range(-100, 100)).collect(),
+		};
+	}
+	
+	// Lay the snippets out into an image
+	pub fn program(self :&Case) -> Vec<isize> {
+		let code_size = 2 + block_size(&self.snippets) + 1;
+		let mut layout = Layout {
+			words : Vec::new(),
+			data_start : code_size,
+			counters : 0,
+			binary_ops : Vec::new(),
+			patches : Vec::new(),
+		};
+		
+		// Point the relative base at the data area, then the code and a halt
+		layout.words.extend_from_slice(&[109, code_size as isize]);
+		layout.block(&self.snippets);
+		layout.words.push(Opcode::Halt.value());
+		
+		// Now that everything has an address, work out what the patches write
+		for &(at, target, opcode) in layout.patches.iter() {
+			let (addr, val) = match layout.binary_ops.get(target % layout.binary_ops.len().max(1)) {
+				Some(&addr) => {
+					let old = layout.words[addr];
+					(addr, old - old % 100 + opcode.value())
+				},
+				None => (code_size, 0),
+			};
+			layout.words[at] = val;
+			layout.words[at + 2] = addr as isize;
+		}
+		
+		layout.words.extend_from_slice(&self.data);
+		layout.words.extend(vec![0; layout.counters]);
+		return layout.words;
+	}
+	
+	// The program as it would appear in a file
+	pub fn source(self :&Case) -> String {
+		let words :Vec<String> = self.program().iter().map(|val| val.to_string()).collect();
+		return words.join(",");
+	}
+}
+
+
+fn block_size(block :&[Snippet]) -> usize {
+	return block.iter().map(snippet_size).sum();
+}
+
+
+fn snippet_size(snippet :&Snippet) -> usize {
+	return match *snippet {
+		Snippet::Op { ref operands, .. } => 1 + operands.len(),
+		Snippet::Shifted { ref body, .. } | Snippet::Far { ref body } => 2 + block_size(body) + 2,
+		Snippet::Skip { .. } => 3,
+		Snippet::Loop { ref body, .. } => 4 + block_size(body) + 4 + 3,
+		Snippet::Patch { .. } => 4,
+	};
+}
+
+
+// Everything needed while turning snippets into words
+struct Layout {
+	words : Vec<isize>,
+	data_start : usize,
+	
+	// How many loops there are, each has a counter after the data area
+	counters : usize,
+	
+	// Addresses of the ops that patches can change, and the patches
+	// themselves as where they are in the code, which op and what to
+	binary_ops : Vec<usize>,
+	patches : Vec<(usize, usize, Opcode)>,
+}
+
+
+impl Layout {
+	// Add an instruction, with operands given as (mode, value)
+	fn emit(self :&mut Layout, opcode :Opcode, operands :&[(isize, isize)]) {
+		let mut ins = opcode.value();
+		let mut scale = 100;
+		for &(mode, _) in operands.iter() {
+			ins += mode * scale;
+			scale *= 10;
+		}
+		self.words.push(ins);
+		self.words.extend(operands.iter().map(|&(_, val)| val));
+	}
+	
+	fn operand(self :&Layout, operand :&Operand, shift :isize) -> (isize, isize) {
+		return match *operand {
+			Operand::Immediate(val) => (1, val),
+			Operand::Data(idx) => (0, (self.data_start + idx) as isize),
+			Operand::Relative(idx) => (2, idx as isize - shift),
+		};
+	}
+	
+	fn op(self :&mut Layout, opcode :Opcode, operands :&[Operand], shift :isize) {
+		if is_binary(opcode) {
+			self.binary_ops.push(self.words.len());
+		}
+		let operands :Vec<(isize, isize)> = operands.iter().map(|operand| self.operand(operand, shift)).collect();
+		self.emit(opcode, &operands);
+	}
+	
+	fn block(self :&mut Layout, block :&[Snippet]) {
+		for (idx, snippet) in block.iter().enumerate() {
+			match *snippet {
+				Snippet::Op { opcode, ref operands } => self.op(opcode, operands, 0),
+				
+				Snippet::Shifted { shift, ref body } => {
+					self.emit(Opcode::AdjustBase, &[(1, shift)]);
+					for op in body.iter() {
+						if let Snippet::Op { opcode, ref operands } = *op {
+							self.op(opcode, operands, shift);
+						}
+					}
+					self.emit(Opcode::AdjustBase, &[(1, -shift)]);
+				},
+				
+				Snippet::Far { ref body } => {
+					let shift = FAR_START as isize - self.data_start as isize;
+					self.emit(Opcode::AdjustBase, &[(1, shift)]);
+					for op in body.iter() {
+						if let Snippet::Op { opcode, ref operands } = *op {
+							self.op(opcode, operands, 0);
+						}
+					}
+					self.emit(Opcode::AdjustBase, &[(1, -shift)]);
+				},
+				
+				Snippet::Skip { opcode, ref cond, skip } => {
+					let end = (idx + 1 + skip).min(block.len());
+					let target = self.words.len() + 3 + block_size(&block[idx + 1..end]);
+					let cond = self.operand(cond, 0);
+					self.emit(opcode, &[cond, (1, target as isize)]);
+				},
+				
+				Snippet::Loop { count, ref body } => {
+					let counter = (self.data_start + DATA_SIZE + self.counters) as isize;
+					self.counters += 1;
+					self.emit(Opcode::Add, &[(1, count), (1, 0), (0, counter)]);
+					let start = self.words.len() as isize;
+					self.block(body);
+					self.emit(Opcode::Add, &[(0, counter), (1, -1), (0, counter)]);
+					self.emit(Opcode::JumpNonZero, &[(0, counter), (1, start)]);
+				},
+				
+				Snippet::Patch { target, opcode } => {
+					// Filled in once all of the ops have been laid out
+					self.patches.push((self.words.len() + 1, target, opcode));
+					self.emit(Opcode::Add, &[(1, 0), (1, 0), (0, 0)]);
+				},
+			}
+		}
+	}
+}
+
+
+// The addresses whose final values are compared, which are the image and the
+// far area
+fn compared(image_len :usize) -> Vec<usize> {
+	return (0..image_len).chain(FAR_START..FAR_START + DATA_SIZE).collect();
+}
+
+
+// Everything that running a program on an engine can be judged on
+#[derive(Debug, PartialEq)]
+struct Outcome {
+	results : Vec<Result<StepResult, IntcodeError>>,
+	outputs : Vec<isize>,
+	memory : Vec<(usize, isize)>,
+	pc : usize,
+	relative_base : isize,
+}
+
+
+impl Outcome {
+	// The final state, written the same way as the transpiled binary does
+	fn summary(self :&Outcome) -> String {
+		return transpile::summary(self.results.last(), &self.outputs, &self.memory);
+	}
+}
+
+
+// Step a machine until it stops, keeping every result
+fn run_engine<M :Memory>(mut machine :Interpreter<M>, inputs :&[isize]) -> Outcome {
+	let image_len = machine.mem.dense().len();
+	machine.extend_inputs(inputs.iter().cloned());
+	let mut results = Vec::new();
+	let mut outputs = Vec::new();
+	while results.len() < MAX_STEPS {
+		let result = machine.step();
+		let done = match result {
+			Ok(StepResult::Continue) => false,
+			Ok(StepResult::Output(val)) => {
+				outputs.push(val);
+				false
+			},
+			_ => true,
+		};
+		results.push(result);
+		if done {
+			break;
+		}
+	}
+	
+	let memory = compared(image_len).into_iter().map(|addr| (addr, machine.get(addr))).collect();
+	return Outcome {
+		results,
+		outputs,
+		memory,
+		pc : machine.pc,
+		relative_base : machine.relative_base,
+	};
+}
+
+
+// Run the case on each of the in-process engines, in the order of ENGINES
+fn run_engines(case :&Case) -> Vec<Outcome> {
+	let source = case.source();
+	let load = |source :&str| match Interpreter::<DenseMemory>::load_with(source) {
+		Err(error) => panic!("Generated program doesn't load: {}", error),
+		Ok(machine) => machine,
+	};
+	let mut step = load(&source);
+	step.disable_decode_cache();
+	let cached = load(&source);
+	let sparse = match Interpreter::<SparseMemory>::load_with(&source) {
+		Err(error) => panic!("Generated program doesn't load: {}", error),
+		Ok(machine) => machine,
+	};
+	return vec![
+		run_engine(step, &case.inputs),
+		run_engine(cached, &case.inputs),
+		run_engine(sparse, &case.inputs),
+	];
+}
+
+
+// Describe the first difference between two outcomes, if there is one
+fn difference(name :&str, expected :&Outcome, actual :&Outcome) -> Option<String> {
+	let steps = expected.results.iter().zip(actual.results.iter()).enumerate();
+	for (idx, (a, b)) in steps {
+		if a != b {
+			return Some(format!("{} differs from {} at step {}: {:?} vs {:?}", name, ENGINES[0], idx, b, a));
+		}
+	}
+	if expected.results.len() != actual.results.len() {
+		return Some(format!("{} ran for {} steps rather than {}", name, actual.results.len(), expected.results.len()));
+	}
+	if expected.outputs != actual.outputs {
+		return Some(format!("{} output {:?} rather than {:?}", name, actual.outputs, expected.outputs));
+	}
+	let words = expected.memory.iter().zip(actual.memory.iter());
+	for (&(addr, a), &(_, b)) in words {
+		if a != b {
+			return Some(format!("{} has {} at [{}] rather than {}", name, b, addr, a));
+		}
+	}
+	if (expected.pc, expected.relative_base) != (actual.pc, actual.relative_base) {
+		return Some(format!("{} stopped with pc={} rb={} rather than pc={} rb={}",
+			name, actual.pc, actual.relative_base, expected.pc, expected.relative_base));
+	}
+	return None;
+}
+
+
+// Compare the outcomes from the in-process engines, returning the first
+// difference
+fn compare(outcomes :&[Outcome]) -> Option<String> {
+	for (name, outcome) in ENGINES.iter().zip(outcomes.iter()).skip(1) {
+		if let Some(difference) = difference(name, &outcomes[0], outcome) {
+			return Some(difference);
+		}
+	}
+	return None;
+}
+
+
+fn check_engines(case :&Case) -> Option<String> {
+	return compare(&run_engines(case));
+}
+
+
+// How the transpiled engine got on with a case
+#[derive(Debug, Clone, PartialEq)]
+enum Verdict {
+	Agreed,
+	Differs(String),
+	
+	// The program changed its own code, so the transpiled engine stopped
+	Skipped,
+}
+
+
+// Build the transpiled form of each case into a single binary and get the
+// final state that each one printed
+fn run_transpiled(cases :&[Case]) -> Result<Vec<String>, String> {
+	let runs :Vec<transpile::Run> = cases.iter()
+		.map(|case| {
+			let program = case.program();
+			return transpile::Run {
+				addresses : compared(program.len()),
+				program,
+				inputs : case.inputs.clone(),
+			};
+		})
+		.collect();
+	return transpile::run_batch(&runs);
+}
+
+
+// Compare the transpiled engine with the step engine, giving a verdict for
+// each of the cases in order
+fn check_transpiled(cases :&[Case]) -> Result<Vec<Verdict>, String> {
+	let lines = run_transpiled(cases)?;
+	return Ok(cases.iter().zip(lines.iter()).map(|(case, line)| {
+		if line.starts_with("NotTranspiled") {
+			return Verdict::Skipped;
+		}
+		let expected = run_engines(case).swap_remove(0).summary();
+		if *line != expected {
+			return Verdict::Differs(format!("transpiled finished with {} rather than {}", line, expected));
+		}
+		return Verdict::Agreed;
+	}).collect());
+}
+
+
+// Every way of making a block a little smaller
+fn smaller_blocks(block :&[Snippet]) -> Vec<Vec<Snippet>> {
+	let mut smaller = Vec::new();
+	let replace = |idx :usize, with :Vec<Snippet>| {
+		let mut block = block.to_vec();
+		block.splice(idx..idx + 1, with);
+		return block;
+	};
+	for (idx, snippet) in block.iter().enumerate() {
+		smaller.push(replace(idx, Vec::new()));
+		match *snippet {
+			Snippet::Loop { count, ref body } => {
+				smaller.push(replace(idx, body.clone()));
+				if count > 1 {
+					smaller.push(replace(idx, vec![Snippet::Loop { count : 1, body : body.clone() }]));
+				}
+				for body in smaller_blocks(body) {
+					smaller.push(replace(idx, vec![Snippet::Loop { count, body }]));
+				}
+			},
+			Snippet::Shifted { shift, ref body } => {
+				smaller.push(replace(idx, body.clone()));
+				for body in smaller_blocks(body).into_iter().filter(|body| !body.is_empty()) {
+					smaller.push(replace(idx, vec![Snippet::Shifted { shift, body }]));
+				}
+			},
+			Snippet::Far { ref body } => {
+				smaller.push(replace(idx, body.clone()));
+				for body in smaller_blocks(body).into_iter().filter(|body| !body.is_empty()) {
+					smaller.push(replace(idx, vec![Snippet::Far { body }]));
+				}
+			},
+			Snippet::Skip { opcode, ref cond, skip } if skip > 1 => {
+				smaller.push(replace(idx, vec![Snippet::Skip { opcode, cond : cond.clone(), skip : 1 }]));
+			},
+			_ => {},
+		}
+	}
+	return smaller;
+}
+
+
+// Every way of making a case a little smaller
+fn smaller_cases(case :&Case) -> Vec<Case> {
+	let mut smaller :Vec<Case> = smaller_blocks(&case.snippets).into_iter()
+		.map(|snippets| Case { snippets, ..case.clone() })
+		.collect();
+	for idx in 0..case.inputs.len() {
+		let mut inputs = case.inputs.clone();
+		inputs.remove(idx);
+		smaller.push(Case { inputs, ..case.clone() });
+	}
+	for idx in 0..case.data.len() {
+		if case.data[idx] != 0 {
+			let mut data = case.data.clone();
+			data[idx] = 0;
+			smaller.push(Case { data, ..case.clone() });
+		}
+	}
+	return smaller;
+}
+
+
+// Keep making a failing case smaller for as long as it still fails. The check
+// is given every smaller version at once and says how each of them differs,
+// and the first one that still does is kept.
+fn shrink<F>(case :Case, difference :String, check :F) -> (Case, String)
+	where F :Fn(&[Case]) -> Vec<Option<String>>
+{
+	let mut case = case;
+	let mut difference = difference;
+	loop {
+		let smaller = smaller_cases(&case);
+		let differences = check(&smaller);
+		let found = smaller.into_iter()
+			.zip(differences)
+			.find_map(|(smaller, difference)| difference.map(|difference| (smaller, difference)));
+		match found {
+			Some((smaller, smaller_difference)) => {
+				case = smaller;
+				difference = smaller_difference;
+			},
+			None => return (case, difference),
+		}
+	}
+}
+
+
+// A case that the engines disagreed on, already shrunk
+#[derive(Debug, Clone)]
+pub struct Failure {
+	pub case : Case,
+	pub difference : String,
+}
+
+
+// What happened over a fuzzing run
+#[derive(Debug, Clone)]
+pub struct Report {
+	pub runs : usize,
+	pub steps : usize,
+	
+	// How many cases were compared with the transpiled engine, or why it
+	// couldn't be used
+	pub transpiled : Result<usize, String>,
+	
+	pub failures : Vec<Failure>,
+}
+
+
+// Generate and check the given number of programs
+pub fn fuzz(runs :usize, seed :u64) -> Report {
+	let mut rng = Rng::new(seed);
+	let cases :Vec<Case> = (0..runs).map(|_| Case::random(&mut rng)).collect();
+	let mut steps = 0;
+	let mut failures = Vec::new();
+	let mut agreed = Vec::new();
+	for case in cases.into_iter() {
+		let outcomes = run_engines(&case);
+		steps += outcomes[0].results.len();
+		match compare(&outcomes) {
+			Some(difference) => {
+				let check = |cases :&[Case]| cases.iter().map(check_engines).collect();
+				let (case, difference) = shrink(case, difference, check);
+				failures.push(Failure { case, difference });
+			},
+			None => agreed.push(case),
+		}
+	}
+	
+	// Only the cases that the others agree on are worth transpiling
+	let mut transpiled = Ok(0);
+	for batch in agreed.chunks(BATCH_SIZE) {
+		let verdicts = match check_transpiled(batch) {
+			Err(error) => {
+				transpiled = Err(error);
+				break;
+			},
+			Ok(verdicts) => verdicts,
+		};
+		for (case, verdict) in batch.iter().zip(verdicts) {
+			match verdict {
+				Verdict::Agreed => transpiled = transpiled.map(|count| count + 1),
+				Verdict::Skipped => {},
+				Verdict::Differs(difference) => {
+					let check = |cases :&[Case]| match check_transpiled(cases) {
+						Ok(verdicts) => verdicts.into_iter()
+							.map(|verdict| match verdict {
+								Verdict::Differs(difference) => Some(difference),
+								_ => None,
+							})
+							.collect(),
+						Err(_) => vec![None; cases.len()],
+					};
+					let (case, difference) = shrink(case.clone(), difference, check);
+					failures.push(Failure { case, difference });
+				},
+			}
+		}
+	}
+	
+	return Report {
+		runs,
+		steps,
+		transpiled,
+		failures,
+	};
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	// Enough for a single transpiled batch, which is most of the time taken
+	const RUNS :usize = 100;
+	const SEED :u64 = 2019;
+	
+	#[test]
+	fn fixed_seed() {
+		let report = fuzz(RUNS, SEED);
+		assert_eq!(report.runs, RUNS);
+		if let Err(ref error) = report.transpiled {
+			panic!("{}", error);
+		}
+		for failure in report.failures.iter() {
+			println!("{}\nProgram: {}\nInputs: {:?}\n", failure.difference, failure.case.source(), failure.case.inputs);
+		}
+		assert!(report.failures.is_empty(), "Engines disagreed on {} programs", report.failures.len());
+	}
+	
+	#[test]
+	fn reaches_the_far_area() {
+		// Write the input relative to a base in the far area and read it back
+		// from there
+		let case = Case {
+			snippets : vec![Snippet::Far { body : vec![
+				Snippet::Op { opcode : Opcode::Input, operands : vec![Operand::Relative(3)] },
+				Snippet::Op { opcode : Opcode::Output, operands : vec![Operand::Relative(3)] },
+			] }],
+			data : vec![0; DATA_SIZE],
+			inputs : vec![42],
+		};
+		let outcomes = run_engines(&case);
+		assert_eq!(compare(&outcomes), None);
+		assert_eq!(outcomes[0].outputs, vec![42]);
+		assert!(outcomes[0].memory.contains(&(FAR_START + 3, 42)));
+	}
+}
@@ -0,0 +1,145 @@
+/**
+ * Storage for the memory of a program.
+ *
+ * Programs can write to any address, so memory has to grow to fit whatever
+ * they touch. A Vec is the quickest option when a program stays close to its
+ * image, but a single write to something like 10^9 would need gigabytes of
+ * zeros to get there. SparseMemory keeps the image in a Vec and everything
+ * past it in pages that only get created when something is written to them.
+ */
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+
+// Words per page of sparse memory
+pub const PAGE_SIZE :usize = 1024;
+
+// How far dense memory is allowed to grow (128MiB), anything past this needs
+// sparse memory
+pub const MAX_DENSE_WORDS :usize = 1 << 24;
+
+
+pub trait Memory :Clone + PartialEq {
+	// Create memory holding the program image, starting at address 0
+	fn from_image(image :Vec<isize>) -> Self;
+	
+	// Read a word, anything that hasn't been written reads as 0
+	fn get(&self, addr :usize) -> isize;
+	
+	// Write a word, making room for it if needed
+	fn set(&mut self, addr :usize, val :isize);
+	
+	// Whether there's room to write to the address
+	fn fits(&self, addr :usize) -> bool;
+	
+	// The part of memory that's stored as a single run from address 0
+	fn dense(&self) -> &[isize];
+	
+	// Feed the contents into a hasher, so that equal memories hash the same
+	fn hash_words<H :Hasher>(&self, state :&mut H);
+}
+
+
+// Everything in a single Vec, grown to fit the highest address written
+#[derive(Debug, Clone, PartialEq)]
+pub struct DenseMemory {
+	words : Vec<isize>,
+}
+
+
+impl Memory for DenseMemory {
+	fn from_image(image :Vec<isize>) -> DenseMemory {
+		return DenseMemory {
+			words : image,
+		};
+	}
+	
+	fn get(&self, addr :usize) -> isize {
+		return match self.words.get(addr) {
+			Some(val) => *val,
+			None => 0,
+		};
+	}
+	
+	fn set(&mut self, addr :usize, val :isize) {
+		if addr >= self.words.len() {
+			self.words.resize(addr + 1, 0);
+		}
+		self.words[addr] = val;
+	}
+	
+	fn fits(&self, addr :usize) -> bool {
+		return addr < MAX_DENSE_WORDS;
+	}
+	
+	fn dense(&self) -> &[isize] {
+		return &self.words;
+	}
+	
+	fn hash_words<H :Hasher>(&self, state :&mut H) {
+		self.words.hash(state);
+	}
+}
+
+
+// The image in a Vec, and anything past it in pages
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseMemory {
+	image : Vec<isize>,
+	pages : HashMap<usize, Box<[isize; PAGE_SIZE]>>,
+}
+
+
+impl Memory for SparseMemory {
+	fn from_image(image :Vec<isize>) -> SparseMemory {
+		return SparseMemory {
+			image,
+			pages : HashMap::new(),
+		};
+	}
+	
+	fn get(&self, addr :usize) -> isize {
+		if addr < self.image.len() {
+			return self.image[addr];
+		}
+		return match self.pages.get(&(addr / PAGE_SIZE)) {
+			Some(page) => page[addr % PAGE_SIZE],
+			None => 0,
+		};
+	}
+	
+	fn set(&mut self, addr :usize, val :isize) {
+		if addr < self.image.len() {
+			self.image[addr] = val;
+			return;
+		}
+		
+		// Writing a zero to a page that doesn't exist wouldn't change anything
+		let key = addr / PAGE_SIZE;
+		if val == 0 && !self.pages.contains_key(&key) {
+			return;
+		}
+		let page = self.pages.entry(key).or_insert_with(|| Box::new([0; PAGE_SIZE]));
+		page[addr % PAGE_SIZE] = val;
+	}
+	
+	fn fits(&self, _addr :usize) -> bool {
+		return true;
+	}
+	
+	fn dense(&self) -> &[isize] {
+		return &self.image;
+	}
+	
+	fn hash_words<H :Hasher>(&self, state :&mut H) {
+		// Pages have to go in a fixed order for the hash to be repeatable
+		self.image.hash(state);
+		let mut keys :Vec<&usize> = self.pages.keys().collect();
+		keys.sort();
+		for key in keys {
+			key.hash(state);
+			self.pages[key].hash(state);
+		}
+	}
+}
@@ -2,14 +2,62 @@
  * Intcode interpreter, used by multiple days.
  */
 
+use std::cell::Cell;
+use std::collections::VecDeque;
 
-// The interpreter object
+mod error;
+pub use self::error::IntcodeError;
+
+pub mod decode;
+use self::decode::{Instruction, Mode, Opcode};
+
+pub mod trace;
+use self::trace::{Trace, TraceEntry};
+
+pub mod profile;
+use self::profile::Profile;
+
+pub mod undo;
+use self::undo::{UndoEntry, UndoLog};
+
+mod cache;
+use self::cache::DecodeCache;
+
+mod cycle;
+use self::cycle::CycleDetector;
+
+pub mod memory;
+use self::memory::{DenseMemory, Memory};
+
+pub mod snapshot;
+
+pub mod device;
+pub mod threaded;
+
+// Tooling for looking at programs, not needed by the days themselves
+pub mod disasm;
+pub mod asm;
+pub mod debugger;
+pub mod cfg;
+pub mod transpile;
+pub mod bench;
+pub mod fuzz;
+pub mod tools;
+
+
+// The interpreter object, with memory kept in a Vec unless asked otherwise
 #[derive(Clone)]
-pub struct Interpreter {
-	mem : Vec<isize>,
+pub struct Interpreter<M :Memory = DenseMemory> {
+	mem : M,
 	pc : usize,
-	next_input : Option<isize>,
+	relative_base : isize,
+	inputs : VecDeque<isize>,
 	outputs :Vec<isize>,
+	trace : Option<Trace>,
+	profile : Option<Profile>,
+	undo : Option<UndoLog>,
+	cache : Option<DecodeCache>,
+	detect_cycles : bool,
 }
 
 
@@ -19,251 +67,687 @@ pub enum StepResult {
 	Continue,
 	Break,
 	Input,
+	Output(isize),
+	
+	// Only from run_with_limit, when the step budget ran out before the
+	// program stopped by itself
+	OutOfSteps,
+	
+	// Only from run_with_limit, when the program is in a loop that it can
+	// never get out of
+	InfiniteLoop,
 }
 
 
 impl Interpreter {
 	// Load the input string into a buffer to be treated as Intcode RAM
-	pub fn load(input :&str) -> Interpreter {
+	pub fn load(input :&str) -> Result<Interpreter, IntcodeError> {
+		return Interpreter::load_with(input);
+	}
+	
+	// Get the raw memory of the program
+	pub fn memory(self :&Interpreter) -> &[isize] {
+		return self.mem.dense();
+	}
+	
+	// Pretty print a program (but not that pretty)
+	#[allow(dead_code)]
+	pub fn dump(self :&Interpreter)
+	{
+		println!("Program (pc={}):", self.pc);
+		for (idx, val) in self.memory().iter().enumerate()
+		{
+			print!("{},\t", val);
+			if (idx & 7) == 7
+			{
+				println!();
+			}
+		}
+		println!();
+	}
+}
+
+
+impl<M :Memory> Interpreter<M> {
+	// Load the input string into whichever kind of memory is wanted, eg:
+	//
+	//   Interpreter::<SparseMemory>::load_with(&input)
+	pub fn load_with(input :&str) -> Result<Interpreter<M>, IntcodeError> {
 		// Transform the elements into integers
 		let mem :Vec<isize> = input
 			.trim() // ignore trailing whitespace
 			.split(',') // split on ','
-			.map(|val| val.parse().unwrap()) // parse each value into an isize
-			.collect(); // combine into a Vec
+			.enumerate() // keep track of where we are for errors
+			.map(|(index, val)| {
+				// parse each value into an isize
+				let val = val.trim();
+				return val.parse().map_err(|_| IntcodeError::Parse {
+					index,
+					token : val.to_string(),
+				});
+			})
+			.collect::<Result<_, _>>()?; // combine into a Vec
 		
-		return Interpreter {
-			mem,
+		let cache = Some(DecodeCache::new(&mem));
+		return Ok(Interpreter {
+			mem : M::from_image(mem),
 			pc : 0,
-			next_input : None,
+			relative_base : 0,
+			inputs : VecDeque::new(),
 			outputs : Vec::new(),
-		};
+			trace : None,
+			profile : None,
+			undo : None,
+			cache,
+			detect_cycles : false,
+		});
+	}
+	
+	// Set a value in memory, making room for it if needed. Unlike a write
+	// from the program, nothing stops this going past MAX_DENSE_WORDS.
+	pub fn set(self :&mut Interpreter<M>, idx :usize, val :isize) {
+		self.mem.set(idx, val);
+		if let Some(ref mut cache) = self.cache {
+			cache.invalidate(idx);
+		}
 	}
 	
-	// Set a value in memory
-	pub fn set(self :&mut Interpreter, idx :usize, val :isize) {
-		self.mem[idx] = val;
+	// Get a value from memory, anything past the end reads as 0
+	pub fn get(self :&Interpreter<M>, idx :usize) -> isize {
+		return self.mem.get(idx);
 	}
 	
-	// Get a value from memory
-	pub fn get(self :&Interpreter, idx :usize) -> isize {
-		return self.mem[idx];
+	// Queue up an input value for the program to read
+	pub fn push_input(self :&mut Interpreter<M>, input :isize) {
+		self.inputs.push_back(input);
 	}
 	
-	// Set the next input value
-	pub fn set_input(self :&mut Interpreter, input :isize) {
-		self.next_input = Some(input);
+	// Queue up several input values, to be read in order
+	pub fn extend_inputs<I>(self :&mut Interpreter<M>, inputs :I)
+		where I :IntoIterator<Item = isize>
+	{
+		self.inputs.extend(inputs);
 	}
 	
 	// Get and clear the outputs from the program
-	pub fn get_outputs(self :&mut Interpreter) -> Vec<isize> {
+	pub fn get_outputs(self :&mut Interpreter<M>) -> Vec<isize> {
 		let mut output = Vec::new();
 		std::mem::swap(&mut output, &mut self.outputs);
 		return output;
 	}
 	
-	// Pretty print a program (but not that pretty)
+	// Get the current program counter
+	pub fn pc(self :&Interpreter<M>) -> usize {
+		return self.pc;
+	}
+	
+	// Get the current relative base
+	pub fn relative_base(self :&Interpreter<M>) -> isize {
+		return self.relative_base;
+	}
+	
+	// Get the inputs that are queued up but haven't been read yet
+	pub fn pending_inputs(self :&Interpreter<M>) -> &VecDeque<isize> {
+		return &self.inputs;
+	}
+	
+	// Start recording executed instructions, keeping only the most recent
+	pub fn enable_trace(self :&mut Interpreter<M>, capacity :usize) {
+		self.trace = Some(Trace::new(capacity));
+	}
+	
+	// Stop recording executed instructions and throw away the log
+	pub fn disable_trace(self :&mut Interpreter<M>) {
+		self.trace = None;
+	}
+	
+	// Get the log of executed instructions, if tracing is enabled
+	pub fn trace(self :&Interpreter<M>) -> Option<&Trace> {
+		return self.trace.as_ref();
+	}
+	
+	// Start counting executed instructions and memory accesses, throwing
+	// away any previous counts
+	pub fn enable_profile(self :&mut Interpreter<M>) {
+		self.profile = Some(Profile::new());
+	}
+	
+	// Stop counting executed instructions and throw away the counts
 	#[allow(dead_code)]
-	pub fn dump(self :&Interpreter)
-	{
-		println!("Program (pc={}):", self.pc);
-		for idx in 0..self.mem.len()
-		{
-			print!("{},\t", self.mem[idx]);
-			if (idx & 7) == 7
-			{
-				println!();
+	pub fn disable_profile(self :&mut Interpreter<M>) {
+		self.profile = None;
+	}
+	
+	// Get the execution counts, if profiling is enabled
+	pub fn profile(self :&Interpreter<M>) -> Option<&Profile> {
+		return self.profile.as_ref();
+	}
+	
+	// Start logging what each instruction changes so that it can be undone,
+	// keeping only the most recent
+	pub fn enable_undo_log(self :&mut Interpreter<M>, capacity :usize) {
+		self.undo = Some(UndoLog::new(capacity));
+	}
+	
+	// Stop logging changes, so nothing that's already run can be undone
+	#[allow(dead_code)]
+	pub fn disable_undo_log(self :&mut Interpreter<M>) {
+		self.undo = None;
+	}
+	
+	// Undo the most recent instruction, putting back anything it wrote, input
+	// it read and output it saved. Outputs that have already been taken with
+	// get_outputs() can't be taken back. Undoing a write that made memory grow
+	// puts a zero back there, but memory stays the size it grew to. Returns
+	// false if there was nothing left to undo.
+	pub fn step_back(self :&mut Interpreter<M>) -> bool {
+		let entry = match self.undo.as_mut().and_then(|undo| undo.pop()) {
+			Some(entry) => entry,
+			None => return false,
+		};
+		
+		self.pc = entry.pc;
+		self.relative_base = entry.relative_base;
+		if let Some((addr, old)) = entry.write {
+			self.set(addr, old);
+		}
+		if let Some(input) = entry.input {
+			self.inputs.push_front(input);
+		}
+		if let Some(idx) = entry.output {
+			if self.outputs.len() == idx + 1 {
+				self.outputs.pop();
 			}
 		}
-		println!();
+		return true;
+	}
+	
+	// Step backwards until the instruction at pc is the next to run. Returns
+	// false if the log ran out first.
+	pub fn run_back_to(self :&mut Interpreter<M>, pc :usize) -> bool {
+		loop {
+			if !self.step_back() {
+				return false;
+			}
+			if self.pc == pc {
+				return true;
+			}
+		}
+	}
+	
+	// Save an output to be read with get_outputs(), noting where it went in
+	// case the instruction that produced it gets undone
+	fn save_output(self :&mut Interpreter<M>, val :isize) {
+		if let Some(entry) = self.undo.as_mut().and_then(|undo| undo.last_mut()) {
+			entry.output = Some(self.outputs.len());
+		}
+		self.outputs.push(val);
+	}
+	
+	// Cache decoded instructions rather than decoding them on every step. The
+	// cache is on by default, so this is only needed to turn it back on.
+	pub fn enable_decode_cache(self :&mut Interpreter<M>) {
+		if self.cache.is_none() {
+			self.cache = Some(DecodeCache::new(self.mem.dense()));
+		}
+	}
+	
+	// Decode every instruction from scratch, mostly useful for comparing
+	pub fn disable_decode_cache(self :&mut Interpreter<M>) {
+		self.cache = None;
+	}
+	
+	// Decode the instruction at pc, going via the cache if it's enabled
+	fn decode(self :&mut Interpreter<M>, pc :usize, ins :isize) -> Result<Instruction, IntcodeError> {
+		if let Some(decoded) = self.cache.as_ref().and_then(|cache| cache.get(pc)) {
+			return Ok(decoded);
+		}
+		let decoded = Instruction::decode(pc, ins)?;
+		if let Some(ref mut cache) = self.cache {
+			cache.insert(pc, decoded);
+		}
+		return Ok(decoded);
+	}
+	
+	// Have run_with_limit look out for programs stuck in a loop, which slows
+	// it down a fair bit
+	pub fn enable_cycle_detection(self :&mut Interpreter<M>) {
+		self.detect_cycles = true;
+	}
+	
+	// Only stop run_with_limit when the budget runs out, which is the default
+	#[allow(dead_code)]
+	pub fn disable_cycle_detection(self :&mut Interpreter<M>) {
+		self.detect_cycles = false;
 	}
 	
 	// Step the program
-	pub fn step(self :&mut Interpreter) -> StepResult {
-		// Read off the next instruction
-		let ins = self.mem[self.pc];
+	pub fn step(self :&mut Interpreter<M>) -> Result<StepResult, IntcodeError> {
+		// Read off the next instruction and decode it
+		let pc = self.pc;
+		let ins = self.get(pc);
+		let decoded = self.decode(pc, ins)?;
+		let modes = decoded.modes;
 		
-		#[derive(Debug, PartialEq)]
-		enum OpMode { Position, Immediate, };
-		let to_op_mode = |val| {
-			match val {
-				0 => OpMode::Position,
-				1 => OpMode::Immediate,
-				_ => panic!("Unknown parameter mode: {} for ins: {} at pc: {}", val, ins, self.pc),
+		// Addresses can't be negative, so don't let them wrap around
+		let to_address = |addr :isize| {
+			if addr < 0 {
+				return Err(IntcodeError::AddressOutOfRange { pc, ins, addr });
 			}
+			return Ok(addr as usize);
 		};
 		
-		// Peel away the parameter modes
-		let operation = ins % 100;
-		let operand_mode_0 = to_op_mode((ins / 100) % 10);
-		let operand_mode_1 = to_op_mode((ins / 1000) % 10);
-		let operand_mode_2 = to_op_mode((ins / 10000) % 10);
+		// Words are fixed size, so any arithmetic that doesn't fit is an error
+		// rather than something to silently wrap around
+		let overflow = || IntcodeError::Overflow { pc, ins };
+		let relative_base = self.relative_base;
+		let relative = |val :isize| relative_base.checked_add(val).ok_or_else(overflow);
 		
-		// Setup read/write methods
-		let read_operand = |val, mode| {
-			match mode {
-				OpMode::Position => self.mem[val as usize],
-				OpMode::Immediate => val,
+		// Setup read/write methods for the operands following the instruction,
+		// noting down the addresses read from if we're being profiled
+		let profiling = self.profile.is_some();
+		let reads = Cell::new([None; 3]);
+		let read_operand = |idx :usize| {
+			let val = self.get(pc + 1 + idx);
+			let addr = match modes[idx] {
+				Mode::Position => to_address(val)?,
+				Mode::Immediate => return Ok(val),
+				Mode::Relative => to_address(relative(val)?)?,
+			};
+			if profiling {
+				let mut addrs = reads.get();
+				addrs[idx] = Some(addr);
+				reads.set(addrs);
+			}
+			return Ok(self.get(addr));
+		};
+		let write_address = |idx :usize| {
+			let val = self.get(pc + 1 + idx);
+			let addr = match modes[idx] {
+				Mode::Position => val,
+				Mode::Relative => relative(val)?,
+				Mode::Immediate => return Err(IntcodeError::InvalidWriteMode { pc, ins }),
+			};
+			
+			// Memory might not be able to grow that far, which is an error
+			// rather than something to run out of memory trying
+			let target = to_address(addr)?;
+			if !self.mem.fits(target) {
+				return Err(IntcodeError::AddressOutOfRange { pc, ins, addr });
 			}
+			return Ok(target);
 		};
 		
-		// Handle the instruction
-		match operation
+		// Handle the instruction, keeping track of the resolved operands and
+		// anything that needs writing back to memory
+		let mut operands = [0; 3];
+		let mut write = None;
+		let mut consumed = None;
+		let result = match decoded.opcode
 		{
-			// Add
-			1 => {
+			Opcode::Add => {
 				// Load the sources and dest
-				let s0 = self.mem[self.pc + 1];
-				let s1 = self.mem[self.pc + 2];
-				assert_eq!(operand_mode_2, OpMode::Position);
-				let d0 = self.mem[self.pc + 3] as usize;
+				let s0 = read_operand(0)?;
+				let s1 = read_operand(1)?;
+				let d0 = write_address(2)?;
+				operands = [s0, s1, d0 as isize];
 				
 				// Do the op
-				self.mem[d0] =
-					read_operand(s0, operand_mode_0) +
-					read_operand(s1, operand_mode_1);
+				write = Some((d0, s0.checked_add(s1).ok_or_else(overflow)?));
 				
 				// Increment to the next ins
 				self.pc += 4;
+				StepResult::Continue
 			},
 			
-			// Mul
-			2 => {
+			Opcode::Mul => {
 				// Load the sources and dest
-				let s0 = self.mem[self.pc + 1];
-				let s1 = self.mem[self.pc + 2];
-				assert_eq!(operand_mode_2, OpMode::Position);
-				let d0 = self.mem[self.pc + 3] as usize;
+				let s0 = read_operand(0)?;
+				let s1 = read_operand(1)?;
+				let d0 = write_address(2)?;
+				operands = [s0, s1, d0 as isize];
 				
 				// Do the op
-				self.mem[d0] = 
-					read_operand(s0, operand_mode_0) *
-					read_operand(s1, operand_mode_1);
+				write = Some((d0, s0.checked_mul(s1).ok_or_else(overflow)?));
 				
 				// Increment to the next ins
 				self.pc += 4;
+				StepResult::Continue
 			},
 			
-			// Input
-			3 => {
+			Opcode::Input => {
 				// Read the next queued input
-				if let Some(input) = self.next_input {
-					self.next_input = None;
-					
+				if let Some(&input) = self.inputs.front() {
 					// Load the dest location
-					assert_eq!(operand_mode_0, OpMode::Position);
-					let d0 = self.mem[self.pc + 1] as usize;
+					let d0 = write_address(0)?;
+					self.inputs.pop_front();
+					consumed = Some(input);
+					operands[0] = d0 as isize;
 					
 					// Store it to memory
-					self.mem[d0] = input;
+					write = Some((d0, input));
 					
 					// Increment to the next ins
 					self.pc += 2;
+					StepResult::Continue
 				} else {
-					// No input ready, so return that we need one
-					return StepResult::Input;
+					// Queue is empty, so return that we need more
+					return Ok(StepResult::Input);
 				}
 			},
 			
-			// Output
-			4 => {
-				// Load the source location
-				let s0 = self.mem[self.pc + 1];
-				
-				// Get the value requested and save it as an output
-				let val = read_operand(s0, operand_mode_0);
-				self.outputs.push(val);
+			Opcode::Output => {
+				// Get the value requested
+				let val = read_operand(0)?;
+				operands[0] = val;
 				
 				// Increment to the next ins
 				self.pc += 2;
+				
+				// Hand it back so that the caller can decide what to do with it
+				StepResult::Output(val)
 			},
 			
-			// JNZ
-			5 => {
+			Opcode::JumpNonZero => {
 				// Load the source and dest
-				let s0 = self.mem[self.pc + 1];
-				let d0 = self.mem[self.pc + 2];
+				let s0 = read_operand(0)?;
+				let d0 = read_operand(1)?;
+				operands = [s0, d0, 0];
 				
 				// Update pc to the new address
-				if read_operand(s0, operand_mode_0) != 0 {
-					self.pc = read_operand(d0, operand_mode_1) as usize;
+				if s0 != 0 {
+					self.pc = to_address(d0)?;
 				} else {
 					self.pc += 3;
 				}
+				StepResult::Continue
 			},
 			
-			// JEZ
-			6 => {
+			Opcode::JumpZero => {
 				// Load the source and dest
-				let s0 = self.mem[self.pc + 1];
-				let d0 = self.mem[self.pc + 2];
+				let s0 = read_operand(0)?;
+				let d0 = read_operand(1)?;
+				operands = [s0, d0, 0];
 				
 				// Update pc to the new address
-				if read_operand(s0, operand_mode_0) == 0 {
-					self.pc = read_operand(d0, operand_mode_1) as usize;
+				if s0 == 0 {
+					self.pc = to_address(d0)?;
 				} else {
 					self.pc += 3;
 				}
+				StepResult::Continue
 			},
 			
-			// Set if less
-			7 => {
-				// Load the source and dest
-				let s0 = self.mem[self.pc + 1];
-				let s1 = self.mem[self.pc + 2];
-				assert_eq!(operand_mode_2, OpMode::Position);
-				let d0 = self.mem[self.pc + 3] as usize;
-				
-				// Calculate the result
-				let val = read_operand(s0, operand_mode_0) < read_operand(s1, operand_mode_1);
+			Opcode::LessThan => {
+				// Load the sources and dest
+				let s0 = read_operand(0)?;
+				let s1 = read_operand(1)?;
+				let d0 = write_address(2)?;
+				operands = [s0, s1, d0 as isize];
 				
-				// Store it to memory
-				self.mem[d0] = if val { 1 } else { 0 };
+				// Store the result to memory
+				write = Some((d0, if s0 < s1 { 1 } else { 0 }));
 				
 				// Increment to the next ins
 				self.pc += 4;
+				StepResult::Continue
 			},
 			
-			// Set if equal
-			8 => {
-				// Load the source and dest
-				let s0 = self.mem[self.pc + 1];
-				let s1 = self.mem[self.pc + 2];
-				assert_eq!(operand_mode_2, OpMode::Position);
-				let d0 = self.mem[self.pc + 3] as usize;
-				
-				// Calculate the result
-				let val = read_operand(s0, operand_mode_0) == read_operand(s1, operand_mode_1);
+			Opcode::Equals => {
+				// Load the sources and dest
+				let s0 = read_operand(0)?;
+				let s1 = read_operand(1)?;
+				let d0 = write_address(2)?;
+				operands = [s0, s1, d0 as isize];
 				
-				// Store it to memory
-				self.mem[d0] = if val { 1 } else { 0 };
+				// Store the result to memory
+				write = Some((d0, if s0 == s1 { 1 } else { 0 }));
 				
 				// Increment to the next ins
 				self.pc += 4;
+				StepResult::Continue
 			},
 			
-			// Break out
-			99 => {
-				self.pc += 1;
-				return StepResult::Break;
+			Opcode::AdjustBase => {
+				// Move the base by the requested amount
+				let s0 = read_operand(0)?;
+				operands[0] = s0;
+				self.relative_base = relative(s0)?;
+				
+				// Increment to the next ins
+				self.pc += 2;
+				StepResult::Continue
 			},
 			
-			// This shouldn't happen
-			_ => panic!("Unknown ins: {} at pc: {}", ins, self.pc),
+			Opcode::Halt => {
+				self.pc += 1;
+				StepResult::Break
+			},
+		};
+		
+		// Remember how to put things back before anything gets overwritten
+		let mem = &self.mem;
+		if let Some(ref mut undo) = self.undo {
+			undo.push(UndoEntry {
+				pc,
+				relative_base,
+				write : write.map(|(addr, _)| (addr, mem.get(addr))),
+				input : consumed,
+				output : None,
+			});
 		}
 		
-		return StepResult::Continue;
+		// Commit any write back to memory
+		if let Some((addr, val)) = write {
+			self.set(addr, val);
+		}
+		
+		// Count what happened if we're being profiled
+		if let Some(ref mut profile) = self.profile {
+			profile.record(pc, decoded.opcode, &reads.get(), write.map(|(addr, _)| addr));
+		}
+		
+		// Record what happened if we're being traced
+		if let Some(ref mut trace) = self.trace {
+			trace.push(TraceEntry {
+				pc,
+				opcode : decoded.opcode,
+				operands,
+				write,
+			});
+		}
+		
+		return Ok(result);
 	}
 	
-	// Executes the program until a break is encountered
-	pub fn run(self :&mut Interpreter) -> StepResult
+	// Executes the program until a break is encountered, saving outputs as
+	// they are produced so they can be read with get_outputs()
+	pub fn run(self :&mut Interpreter<M>) -> Result<StepResult, IntcodeError>
 	{
 		loop {
-			let result = self.step();
+			match self.step()? {
+				StepResult::Continue => {},
+				StepResult::Output(val) => self.save_output(val),
+				result => return Ok(result),
+			}
+		}
+	}
+	
+	// As run, but gives up after max_steps instructions. If cycle detection
+	// is enabled, it also gives up as soon as it's certain the program will
+	// never stop.
+	pub fn run_with_limit(self :&mut Interpreter<M>, max_steps :usize) -> Result<StepResult, IntcodeError>
+	{
+		let mut detector = if self.detect_cycles { Some(CycleDetector::new()) } else { None };
+		for _ in 0..max_steps {
+			let pc = self.pc;
+			match self.step()? {
+				StepResult::Continue => {},
+				StepResult::Output(val) => self.save_output(val),
+				result => return Ok(result),
+			}
+			
+			// Only a jump can move backwards, and any loop has to
+			if let Some(ref mut detector) = detector {
+				if self.pc <= pc && detector.check(self.pc, self.relative_base, &self.mem, &self.inputs) {
+					return Ok(StepResult::InfiniteLoop);
+				}
+			}
+		}
+		return Ok(StepResult::OutOfSteps);
+	}
+	
+	// Executes the program until it produces an output, which is returned
+	// directly rather than being saved, or until it stops for any other reason
+	pub fn run_until_output(self :&mut Interpreter<M>) -> Result<StepResult, IntcodeError>
+	{
+		loop {
+			let result = self.step()?;
 			if result != StepResult::Continue {
-				return result;
+				return Ok(result);
 			}
 		}
 	}
 }
 
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	// Run a program to completion, getting back how it stopped and what it
+	// output
+	fn run(source :&str) -> (Result<StepResult, IntcodeError>, Vec<isize>) {
+		let mut machine = Interpreter::load(source).unwrap();
+		let result = machine.run();
+		return (result, machine.get_outputs());
+	}
+	
+	#[test]
+	fn run_until_output() {
+		let mut machine = Interpreter::load("104,1,3,7,4,7,99,0").unwrap();
+		assert_eq!(machine.run_until_output(), Ok(StepResult::Output(1)));
+		assert_eq!(machine.run_until_output(), Ok(StepResult::Input));
+		machine.push_input(5);
+		assert_eq!(machine.run_until_output(), Ok(StepResult::Output(5)));
+		assert_eq!(machine.run_until_output(), Ok(StepResult::Break));
+		
+		// Nothing was saved, it was all handed back
+		assert_eq!(machine.get_outputs(), Vec::<isize>::new());
+	}
+	
+	// Jumps back to itself forever
+	const SPIN :&str = "1105,1,0";
+	
+	// Counts down from 5 at address 20 and then halts
+	const COUNTDOWN :&str = "1101,5,0,20,1001,20,-1,20,1005,20,4,99";
+	
+	#[test]
+	fn out_of_steps() {
+		let mut machine = Interpreter::load(SPIN).unwrap();
+		assert_eq!(machine.run_with_limit(1000), Ok(StepResult::OutOfSteps));
+		
+		// The countdown needs 12 steps, so it only just makes it
+		let mut machine = Interpreter::load(COUNTDOWN).unwrap();
+		assert_eq!(machine.run_with_limit(11), Ok(StepResult::OutOfSteps));
+		let mut machine = Interpreter::load(COUNTDOWN).unwrap();
+		assert_eq!(machine.run_with_limit(12), Ok(StepResult::Break));
+	}
+	
+	#[test]
+	fn infinite_loop() {
+		let mut machine = Interpreter::load(SPIN).unwrap();
+		machine.enable_cycle_detection();
+		assert_eq!(machine.run_with_limit(1000), Ok(StepResult::InfiniteLoop));
+		assert_eq!(machine.pc, 0);
+	}
+	
+	#[test]
+	fn loops_that_get_somewhere() {
+		// Jumps back while counting down, but then stops
+		let mut machine = Interpreter::load(COUNTDOWN).unwrap();
+		machine.enable_cycle_detection();
+		assert_eq!(machine.run_with_limit(1000), Ok(StepResult::Break));
+		assert_eq!(machine.get(20), 0);
+		
+		// Never stops, but the relative base never comes back round either
+		let mut machine = Interpreter::load("109,1,1105,1,0").unwrap();
+		machine.enable_cycle_detection();
+		assert_eq!(machine.run_with_limit(1000), Ok(StepResult::OutOfSteps));
+		
+		// Eats an input each time round, so it's waiting on more input rather
+		// than looping
+		let mut machine = Interpreter::load("3,20,1105,1,0").unwrap();
+		machine.enable_cycle_detection();
+		machine.extend_inputs(vec![1, 1, 1]);
+		assert_eq!(machine.run_with_limit(1000), Ok(StepResult::Input));
+	}
+	
+	// Reads two numbers past the end of the image and outputs their sum
+	const SUM :&str = "3,20,3,21,1,20,21,30,4,30,99";
+	
+	#[test]
+	fn undo_everything() {
+		let mut machine = Interpreter::load(SUM).unwrap();
+		let image = machine.memory().to_vec();
+		machine.enable_undo_log(100);
+		machine.extend_inputs(vec![4, 5]);
+		assert_eq!(machine.run(), Ok(StepResult::Break));
+		assert_eq!(machine.get(30), 9);
+		
+		// The inputs go back on the queue in order, and the output is gone
+		assert!(machine.run_back_to(0));
+		assert_eq!(machine.pc, 0);
+		assert_eq!(machine.inputs, vec![4, 5]);
+		assert_eq!(machine.get_outputs(), Vec::<isize>::new());
+		assert_eq!(&machine.memory()[..image.len()], &image[..]);
+		assert!(machine.memory()[image.len()..].iter().all(|&val| val == 0));
+		
+		// Nothing left to go back to
+		assert!(!machine.step_back());
+	}
+	
+	#[test]
+	fn undo_and_rerun() {
+		let mut machine = Interpreter::load(SUM).unwrap();
+		machine.enable_undo_log(100);
+		machine.extend_inputs(vec![4, 5]);
+		assert_eq!(machine.run(), Ok(StepResult::Break));
+		
+		// Back over the halt and the output
+		assert!(machine.step_back());
+		assert!(machine.step_back());
+		assert_eq!(machine.pc, 8);
+		assert_eq!(machine.get_outputs(), Vec::<isize>::new());
+		
+		// Back to before the second input was read, then give it another
+		assert!(machine.run_back_to(2));
+		assert_eq!(machine.inputs, vec![5]);
+		assert_eq!(machine.get(21), 0);
+		machine.inputs.clear();
+		machine.push_input(10);
+		assert_eq!(machine.run(), Ok(StepResult::Break));
+		assert_eq!(machine.get_outputs(), vec![14]);
+	}
+	
+	#[test]
+	fn day9_quine() {
+		let source = "109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99";
+		let program = Interpreter::load(source).unwrap().memory().to_vec();
+		assert_eq!(run(source), (Ok(StepResult::Break), program));
+	}
+	
+	#[test]
+	fn day9_large_numbers() {
+		assert_eq!(run("1102,34915192,34915192,7,4,7,99,0"), (Ok(StepResult::Break), vec![1219070632396864]));
+		assert_eq!(run("104,1125899906842624,99"), (Ok(StepResult::Break), vec![1125899906842624]));
+	}
+	
+	#[test]
+	fn overflow() {
+		assert_eq!(run("1101,9223372036854775807,1,5,99,0").0,
+			Err(IntcodeError::Overflow { pc : 0, ins : 1101 }));
+		assert_eq!(run("104,1,1102,4611686018427387904,2,7,99,0"),
+			(Err(IntcodeError::Overflow { pc : 2, ins : 1102 }), vec![1]));
+	}
+}
@@ -0,0 +1,117 @@
+/**
+ * Profiler for finding out where a program spends its time, by counting how
+ * often each instruction and opcode runs and how often each address is read
+ * or written.
+ *
+ * Reads only count memory accessed through position or relative operands,
+ * fetching the instruction and its operand words isn't included since that's
+ * already covered by the execution counts.
+ */
+
+use std::collections::HashMap;
+use super::cfg;
+use super::decode::Opcode;
+use super::disasm;
+
+
+#[derive(Debug, Clone)]
+pub struct Profile {
+	steps : usize,
+	executions : HashMap<usize, usize>,
+	opcodes : HashMap<Opcode, usize>,
+	reads : HashMap<usize, usize>,
+	writes : HashMap<usize, usize>,
+}
+
+
+// Sort counts with the highest first, and then by address to keep it stable
+fn hottest<K :Copy + Ord>(counts :&HashMap<K, usize>, top :usize) -> Vec<(K, usize)> {
+	let mut sorted :Vec<(K, usize)> = counts.iter().map(|(&key, &count)| (key, count)).collect();
+	sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+	sorted.truncate(top);
+	return sorted;
+}
+
+
+impl Profile {
+	pub fn new() -> Profile {
+		return Profile {
+			steps : 0,
+			executions : HashMap::new(),
+			opcodes : HashMap::new(),
+			reads : HashMap::new(),
+			writes : HashMap::new(),
+		};
+	}
+	
+	// Count an executed instruction, along with the addresses it read from
+	// and wrote to
+	pub fn record(self :&mut Profile, pc :usize, opcode :Opcode, reads :&[Option<usize>], write :Option<usize>) {
+		self.steps += 1;
+		*self.executions.entry(pc).or_insert(0) += 1;
+		*self.opcodes.entry(opcode).or_insert(0) += 1;
+		for &addr in reads.iter().flatten() {
+			*self.reads.entry(addr).or_insert(0) += 1;
+		}
+		if let Some(addr) = write {
+			*self.writes.entry(addr).or_insert(0) += 1;
+		}
+	}
+	
+	// Write out a summary of the profile, with the top instructions and
+	// addresses annotated from the program's memory. Code that was never
+	// executed is only what can be found by following the control flow from
+	// address 0, so anything only reached by an indirect jump is left out.
+	pub fn report(self :&Profile, mem :&[isize], top :usize) -> String {
+		let percent = |count :usize| 100.0 * count as f64 / self.steps.max(1) as f64;
+		let mut output = format!("Steps: {}\n", self.steps);
+		
+		output += "\nOpcodes:\n";
+		let mut opcodes :Vec<(Opcode, usize)> = self.opcodes.iter().map(|(&opcode, &count)| (opcode, count)).collect();
+		opcodes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.value().cmp(&b.0.value())));
+		for (opcode, count) in opcodes {
+			output += &format!("  {:<4} {:>12} {:>6.2}%\n", opcode.mnemonic(), count, percent(count));
+		}
+		
+		output += &format!("\nHot instructions (top {}):\n", top);
+		for (pc, count) in hottest(&self.executions, top) {
+			let line = match disasm::disassemble(mem, pc).next() {
+				Some(line) => line.to_string(),
+				None => format!("{:04}: ?", pc),
+			};
+			output += &format!("  {:>12} {:>6.2}%  {}\n", count, percent(count), line);
+		}
+		
+		output += &format!("\nMost read addresses (top {}):\n", top);
+		for (addr, count) in hottest(&self.reads, top) {
+			output += &format!("  [{:04}] {:>12}\n", addr, count);
+		}
+		
+		output += &format!("\nMost written addresses (top {}):\n", top);
+		for (addr, count) in hottest(&self.writes, top) {
+			output += &format!("  [{:04}] {:>12}\n", addr, count);
+		}
+		
+		// Merge runs of instructions that follow on from each other
+		let mut ranges :Vec<(usize, usize, usize)> = Vec::new();
+		let graph = cfg::analyse(mem, 0);
+		for line in graph.blocks.values().flat_map(|block| block.lines.iter()) {
+			if self.executions.contains_key(&line.addr) {
+				continue;
+			}
+			match ranges.last_mut() {
+				Some(&mut (_, ref mut end, ref mut count)) if *end == line.addr => {
+					*end += line.size;
+					*count += 1;
+				},
+				_ => ranges.push((line.addr, line.addr + line.size, 1)),
+			}
+		}
+		
+		output += "\nNever executed:\n";
+		for (start, end, count) in ranges {
+			output += &format!("  {:04}-{:04} {:>6} instructions\n", start, end - 1, count);
+		}
+		return output;
+	}
+}
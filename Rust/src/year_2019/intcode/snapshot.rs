@@ -0,0 +1,252 @@
+/**
+ * Saving and restoring the full state of a machine, so that it can be picked
+ * up again later, possibly by a different process.
+ *
+ * The format is plain text, one field per line after a version header:
+ *
+ *   intcode-snapshot v1
+ *   pc 12
+ *   rb 100
+ *   inputs 5,6
+ *   outputs
+ *   mem 109,100,203,1,...
+ *
+ * Anything with a different version is rejected rather than guessed at.
+ */
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use super::Interpreter;
+use super::cache::DecodeCache;
+use super::memory::{DenseMemory, Memory};
+
+
+const HEADER :&str = "intcode-snapshot";
+const VERSION :usize = 1;
+
+
+// Problems with a snapshot
+#[derive(Debug, Clone, PartialEq)]
+pub enum SnapshotError {
+	// The snapshot was written by a different version
+	Version { found :String },
+	
+	// Something in the snapshot didn't make sense, line is 1-based
+	Format { line :usize, message :String },
+}
+
+
+impl fmt::Display for SnapshotError {
+	fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			SnapshotError::Version { ref found } =>
+				write!(f, "Unsupported snapshot version: {} (expected v{})", found, VERSION),
+			SnapshotError::Format { line, ref message } =>
+				write!(f, "Bad snapshot at line {}: {}", line, message),
+		}
+	}
+}
+
+
+impl std::error::Error for SnapshotError {}
+
+
+// Join a list of values into a comma separated string
+fn join<'a, I>(values :I) -> String
+	where I :Iterator<Item = &'a isize>
+{
+	let values :Vec<String> = values.map(|val| val.to_string()).collect();
+	return values.join(",");
+}
+
+
+// Parse a single value
+fn parse<T :FromStr>(text :&str, line :usize) -> Result<T, SnapshotError> {
+	return text.parse().map_err(|_| SnapshotError::Format {
+		line,
+		message : format!("Invalid value {:?}", text),
+	});
+}
+
+
+// Split a comma separated string back into values
+fn split(text :&str, line :usize) -> Result<Vec<isize>, SnapshotError> {
+	if text.is_empty() {
+		return Ok(Vec::new());
+	}
+	return text.split(',').map(|val| parse(val.trim(), line)).collect();
+}
+
+
+impl Interpreter {
+	// Save the state of the machine
+	pub fn save(self :&Interpreter) -> String {
+		let mut output = String::new();
+		output += &format!("{} v{}\n", HEADER, VERSION);
+		output += &format!("pc {}\n", self.pc);
+		output += &format!("rb {}\n", self.relative_base);
+		output += &format!("inputs {}\n", join(self.inputs.iter()));
+		output += &format!("outputs {}\n", join(self.outputs.iter()));
+		output += &format!("mem {}\n", join(self.memory().iter()));
+		return output;
+	}
+	
+	// Restore a machine from a saved state
+	pub fn restore(snapshot :&str) -> Result<Interpreter, SnapshotError> {
+		let mut lines = snapshot.lines().enumerate().map(|(idx, text)| (idx + 1, text.trim()));
+		
+		// Check that we know how to read it
+		match lines.next() {
+			Some((_, text)) if text.starts_with(HEADER) => {
+				let found = text[HEADER.len()..].trim();
+				if found != format!("v{}", VERSION) {
+					return Err(SnapshotError::Version { found : found.to_string() });
+				}
+			},
+			_ => return Err(SnapshotError::Format {
+				line : 1,
+				message : "Missing snapshot header".to_string(),
+			}),
+		}
+		
+		// Read in each of the fields
+		let mut pc = None;
+		let mut relative_base = None;
+		let mut inputs = None;
+		let mut outputs = None;
+		let mut mem = None;
+		let mut last_line = 1;
+		for (line, text) in lines {
+			last_line = line;
+			if text.is_empty() {
+				continue;
+			}
+			
+			let (key, value) = match text.find(' ') {
+				Some(pos) => (&text[..pos], text[pos + 1..].trim()),
+				None => (text, ""),
+			};
+			let format_error = |message :String| SnapshotError::Format { line, message };
+			
+			let duplicate = match key {
+				"pc" => pc.replace(parse(value, line)?).is_some(),
+				"rb" => relative_base.replace(parse(value, line)?).is_some(),
+				"inputs" => inputs.replace(split(value, line)?).is_some(),
+				"outputs" => outputs.replace(split(value, line)?).is_some(),
+				"mem" => mem.replace(split(value, line)?).is_some(),
+				_ => return Err(format_error(format!("Unknown field {:?}", key))),
+			};
+			if duplicate {
+				return Err(format_error(format!("Field {:?} given twice", key)));
+			}
+		}
+		
+		// Everything has to be there
+		let missing = |name :&str| SnapshotError::Format {
+			line : last_line,
+			message : format!("Missing field {:?}", name),
+		};
+		let mem = mem.ok_or_else(|| missing("mem"))?;
+		let cache = Some(DecodeCache::new(&mem));
+		return Ok(Interpreter {
+			mem : DenseMemory::from_image(mem),
+			pc : pc.ok_or_else(|| missing("pc"))?,
+			relative_base : relative_base.ok_or_else(|| missing("rb"))?,
+			inputs : VecDeque::from(inputs.ok_or_else(|| missing("inputs"))?),
+			outputs : outputs.ok_or_else(|| missing("outputs"))?,
+			trace : None,
+			profile : None,
+			undo : None,
+			cache,
+			detect_cycles : false,
+		});
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::StepResult;
+	
+	// Adds pairs of inputs until it's given a 0, keeping them all relative to
+	// the base so it has to be restored too
+	const ADDER :&str = "109,20,203,0,1205,0,9,99,0,203,1,22201,0,1,2,204,2,1105,1,2";
+	
+	const SAVED :&str = "intcode-snapshot v1\npc 2\nrb 20\ninputs 5,6\noutputs\nmem 7,8\n";
+	
+	#[test]
+	fn round_trip() {
+		let mut machine = Interpreter::load(ADDER).unwrap();
+		machine.extend_inputs(vec![1, 2, 3]);
+		assert_eq!(machine.run(), Ok(StepResult::Input));
+		assert_eq!(machine.outputs, vec![3]);
+		
+		let snapshot = machine.save();
+		let mut restored = Interpreter::restore(&snapshot).unwrap();
+		assert_eq!(restored.pc, machine.pc);
+		assert_eq!(restored.relative_base, machine.relative_base);
+		assert_eq!(restored.inputs, machine.inputs);
+		assert_eq!(restored.outputs, machine.outputs);
+		assert_eq!(restored.memory(), machine.memory());
+		assert_eq!(restored.save(), snapshot);
+		
+		// Both carry on in the same way
+		for machine in [&mut machine, &mut restored] {
+			machine.extend_inputs(vec![4, 0]);
+			assert_eq!(machine.run(), Ok(StepResult::Break));
+			assert_eq!(machine.get_outputs(), vec![3, 7]);
+		}
+	}
+	
+	#[test]
+	fn blank_lines_and_empty_lists() {
+		let machine = Interpreter::restore(&SAVED.replace("\n", "\n\n")).unwrap();
+		assert_eq!(machine.pc, 2);
+		assert_eq!(machine.relative_base, 20);
+		assert_eq!(machine.inputs, vec![5, 6]);
+		assert!(machine.outputs.is_empty());
+		assert_eq!(machine.memory(), &[7, 8]);
+	}
+	
+	#[test]
+	fn version_mismatch() {
+		let snapshot = SAVED.replace("v1", "v2");
+		assert_eq!(Interpreter::restore(&snapshot).err(), Some(SnapshotError::Version { found : "v2".to_string() }));
+		assert_eq!(Interpreter::restore("intcode-snapshot\n").err(), Some(SnapshotError::Version { found : "".to_string() }));
+	}
+	
+	#[test]
+	fn missing_header() {
+		let snapshot = SAVED.replace("intcode-snapshot v1\n", "");
+		assert!(matches!(Interpreter::restore(&snapshot), Err(SnapshotError::Format { line : 1, .. })));
+		assert!(matches!(Interpreter::restore(""), Err(SnapshotError::Format { line : 1, .. })));
+	}
+	
+	#[test]
+	fn missing_field() {
+		let snapshot = SAVED.replace("rb 20\n", "");
+		assert_eq!(Interpreter::restore(&snapshot).err(), Some(SnapshotError::Format {
+			line : 5,
+			message : "Missing field \"rb\"".to_string(),
+		}));
+	}
+	
+	#[test]
+	fn duplicate_field() {
+		let snapshot = SAVED.replace("rb 20\n", "rb 20\npc 3\n");
+		assert_eq!(Interpreter::restore(&snapshot).err(), Some(SnapshotError::Format {
+			line : 4,
+			message : "Field \"pc\" given twice".to_string(),
+		}));
+	}
+	
+	#[test]
+	fn bad_fields() {
+		let unknown = SAVED.replace("rb 20", "base 20");
+		assert!(matches!(Interpreter::restore(&unknown), Err(SnapshotError::Format { line : 3, .. })));
+		let invalid = SAVED.replace("inputs 5,6", "inputs 5,x");
+		assert!(matches!(Interpreter::restore(&invalid), Err(SnapshotError::Format { line : 4, .. })));
+	}
+}
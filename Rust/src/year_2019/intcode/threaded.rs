@@ -0,0 +1,228 @@
+/**
+ * Running machines on their own threads, talking over channels.
+ *
+ * Each machine owns the receiving end of its input channel, and anything can
+ * feed it by holding a sender from input(). Machines are wired together by
+ * spawning one with the input of the next as its output, so chains, feedback
+ * loops and anything else can be built up without caring how many outputs a
+ * machine produces per input:
+ *
+ *   let a = ThreadedMachine::new(program.clone());
+ *   let b = ThreadedMachine::new(program.clone());
+ *   let a_input = a.input();
+ *   let a = a.spawn_into(&b);
+ *   let b = b.spawn(a_input);
+ *
+ * Once a machine has stopped, its input channel is handed back so that
+ * anything sent to it afterwards (like the last output of a feedback loop)
+ * can be collected once everything else has been joined.
+ *
+ * A loop where every machine is waiting on the one before it would wait
+ * forever, since each of them is holding a sender for the next. Machines that
+ * are put in the same Group check their inputs and send their outputs under a
+ * shared lock, so when every one of them is waiting and nothing has been sent
+ * since they looked, they all stop as if their input had run out instead.
+ */
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use super::{Interpreter, IntcodeError, StepResult};
+use super::device::{FnDevice, Machine};
+
+
+// A machine that has its input channel set up but hasn't started running
+pub struct ThreadedMachine {
+	interpreter : Interpreter,
+	sender : Sender<isize>,
+	receiver : Receiver<isize>,
+	group : Option<Group>,
+}
+
+
+// Machines that feed each other, so that it can be told when they're all
+// stuck waiting on each other. Every machine in a group has to be spawned,
+// and anything from outside the group has to be sent before that.
+#[derive(Clone)]
+pub struct Group {
+	shared : Arc<(Mutex<GroupState>, Condvar)>,
+}
+
+
+#[derive(Default)]
+struct GroupState {
+	// Machines that have joined and not stopped yet
+	running : usize,
+	
+	// How many of them have found their input empty since anything changed
+	waiting : usize,
+	
+	// Bumped whenever something is sent or a machine stops
+	changes : usize,
+	
+	stuck : bool,
+}
+
+
+// A machine that's been started on its own thread
+pub struct MachineHandle {
+	handle : JoinHandle<Result<Finished, IntcodeError>>,
+}
+
+
+// What a machine left behind once it stopped
+pub struct Finished {
+	// Break if the program finished, or Input if it wanted more input after
+	// everything feeding it had gone away or its group got stuck
+	pub result : StepResult,
+	
+	// The input channel, so that anything sent after the machine stopped
+	// reading can still be picked up
+	pub inputs : Receiver<isize>,
+	
+	// Everything the machine sent out, whether or not anyone received it
+	pub outputs : Vec<isize>,
+}
+
+
+impl ThreadedMachine {
+	pub fn new(interpreter :Interpreter) -> ThreadedMachine {
+		let (sender, receiver) = channel();
+		return ThreadedMachine {
+			interpreter,
+			sender,
+			receiver,
+			group : None,
+		};
+	}
+	
+	// Put the machine in a group with the others it's wired up to
+	pub fn join_group(self :&mut ThreadedMachine, group :&Group) {
+		group.lock().running += 1;
+		self.group = Some(group.clone());
+	}
+	
+	// Get a sender that feeds inputs to this machine
+	pub fn input(self :&ThreadedMachine) -> Sender<isize> {
+		return self.sender.clone();
+	}
+	
+	// Start running on a new thread, sending all outputs to the given channel
+	pub fn spawn(self, output :Sender<isize>) -> MachineHandle {
+		// Only other machines should be able to feed us, otherwise we'd never
+		// notice when there's no more input coming
+		let ThreadedMachine { interpreter, sender, receiver, group } = self;
+		drop(sender);
+		
+		let handle = thread::spawn(move || {
+			let mut outputs = Vec::new();
+			let result = {
+				// Block waiting for each input, and don't worry if nobody is
+				// listening to the outputs anymore
+				let device = FnDevice::new(
+					|| match group {
+						Some(ref group) => group.receive(&receiver),
+						None => receiver.recv().ok(),
+					},
+					|val| {
+						outputs.push(val);
+						match group {
+							Some(ref group) => group.send(&output, val),
+							None => { let _ = output.send(val); },
+						}
+					});
+				Machine::new(interpreter, device).run()
+			};
+			
+			// Let the next machine see that nothing more is coming from us
+			drop(output);
+			if let Some(ref group) = group {
+				group.stopped();
+			}
+			
+			return Ok(Finished {
+				result : result?,
+				inputs : receiver,
+				outputs,
+			});
+		});
+		
+		return MachineHandle { handle };
+	}
+	
+	// Start running on a new thread, sending all outputs to another machine
+	pub fn spawn_into(self, next :&ThreadedMachine) -> MachineHandle {
+		return self.spawn(next.input());
+	}
+}
+
+
+impl MachineHandle {
+	// Wait for the machine to stop
+	pub fn join(self) -> Result<Finished, IntcodeError> {
+		return match self.handle.join() {
+			Ok(result) => result,
+			Err(_) => panic!("Intcode machine thread panicked"),
+		};
+	}
+}
+
+
+impl Group {
+	pub fn new() -> Group {
+		return Group {
+			shared : Arc::new((Mutex::new(GroupState::default()), Condvar::new())),
+		};
+	}
+	
+	fn lock(self :&Group) -> MutexGuard<'_, GroupState> {
+		return self.shared.0.lock().expect("Machine group lock poisoned");
+	}
+	
+	// Something happened that might let the waiting machines carry on, so
+	// they all have to look at their inputs again
+	fn changed(self :&Group, state :&mut GroupState) {
+		state.changes += 1;
+		state.waiting = 0;
+		self.shared.1.notify_all();
+	}
+	
+	// Wait for an input, or None if nothing is ever going to send one
+	fn receive(self :&Group, receiver :&Receiver<isize>) -> Option<isize> {
+		let mut state = self.lock();
+		loop {
+			match receiver.try_recv() {
+				Ok(val) => return Some(val),
+				Err(TryRecvError::Disconnected) => return None,
+				Err(TryRecvError::Empty) => {},
+			}
+			if state.stuck {
+				return None;
+			}
+			
+			// If everyone else has looked since anything was sent, they're
+			// all waiting on each other
+			state.waiting += 1;
+			if state.waiting == state.running {
+				state.stuck = true;
+				self.shared.1.notify_all();
+				return None;
+			}
+			let changes = state.changes;
+			state = self.shared.1.wait_while(state, |state| state.changes == changes && !state.stuck)
+				.expect("Machine group lock poisoned");
+		}
+	}
+	
+	fn send(self :&Group, output :&Sender<isize>, val :isize) {
+		let mut state = self.lock();
+		let _ = output.send(val);
+		self.changed(&mut state);
+	}
+	
+	fn stopped(self :&Group) {
+		let mut state = self.lock();
+		state.running -= 1;
+		self.changed(&mut state);
+	}
+}
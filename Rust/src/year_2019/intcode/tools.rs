@@ -0,0 +1,229 @@
+/**
+ * Command line entry points for the Intcode tooling, for example:
+ *
+ *   aoc disasm inputs/day5.txt
+ *   aoc asm program.asm
+ *   aoc cfg inputs/day5.txt | dot -Tsvg > day5.svg
+ *   aoc debug inputs/day5.txt < script.txt
+ *   aoc transpile inputs/day7.txt > src/day7_amp.rs
+ *   aoc run inputs/day5.txt
+ *   aoc bench inputs/day5.txt 5
+ *   aoc profile inputs/day9.txt 2
+ *   aoc fuzz 1000
+ */
+
+use std::io;
+use super::{asm, bench, cfg, disasm, fuzz, transpile, Interpreter, IntcodeError};
+use super::memory::SparseMemory;
+use super::debugger::Debugger;
+use super::device::{AsciiDevice, IoDevice, LineDevice, Machine};
+
+
+// How many of the hottest instructions and addresses to show when profiling
+const PROFILE_TOP :usize = 20;
+
+
+// Load a file, giving up if it can't be read
+fn load_file(path :&str) -> String
+{
+	return match std::fs::read_to_string(path)
+	{
+		Err(error) => panic!("Failed to open {}: {}", path, error),
+		Ok(string) => string,
+	};
+}
+
+
+// Load a program from a file
+fn load_program(path :&str) -> Interpreter
+{
+	return match Interpreter::load(&load_file(path))
+	{
+		Err(error) => panic!("Failed to load {}: {}", path, error),
+		Ok(program) => program,
+	};
+}
+
+
+// Print out how to use the tools
+fn usage()
+{
+	println!("Usage:");
+	println!("\tdisasm <program>\tPrint a listing of the program");
+	println!("\tasm <source>\t\tAssemble the source into a program");
+	println!("\tcfg <program>\t\tPrint the control flow graph of the program as DOT");
+	println!("\tdebug <program>\t\tDebug the program, reading commands from stdin");
+	println!("\ttranspile <program>\tTranslate the program into Rust source");
+	println!("\trun <program>\t\tRun the program, with a number per line of input/output");
+	println!("\tascii <program>\t\tRun the program, with input/output as ASCII text");
+	println!("\tbench <program> [input...]\tTime the program with different interpreter setups");
+	println!("\tprofile <program> [input...]\tRun the program and report where it spent its time");
+	println!("\tfuzz <runs> [seed]\tCompare the ways of running programs on random ones");
+}
+
+
+// Run a program until it finishes or runs out of input
+fn run_program<D :IoDevice>(path :&str, device :D)
+{
+	let mut machine = Machine::new(load_program(path), device);
+	match machine.run() {
+		Err(error) => panic!("Program failed: {}", error),
+		Ok(result) => println!("Finished with: {:?}", result),
+	}
+}
+
+
+// Time a program with and without the decode cache, and with each kind of
+// memory
+fn bench_program(path :&str, inputs :&[isize]) -> Result<String, IntcodeError>
+{
+	let dense = load_program(path);
+	let sparse = match Interpreter::<SparseMemory>::load_with(&load_file(path))
+	{
+		Err(error) => panic!("Failed to load {}: {}", path, error),
+		Ok(program) => program,
+	};
+	
+	let duration = bench::DEFAULT_DURATION;
+	let timings = [
+		("cache off, dense", bench::time(&dense, inputs, duration, |machine| machine.disable_decode_cache())?),
+		("cache on, dense", bench::time(&dense, inputs, duration, |machine| machine.enable_decode_cache())?),
+		("cache off, sparse", bench::time(&sparse, inputs, duration, |machine| machine.disable_decode_cache())?),
+		("cache on, sparse", bench::time(&sparse, inputs, duration, |machine| machine.enable_decode_cache())?),
+	];
+	return Ok(bench::report(&timings));
+}
+
+
+// Parse numbers given on the command line to feed to a program
+fn parse_inputs(args :&[String]) -> Vec<isize>
+{
+	return args.iter()
+		.map(|arg| match arg.parse() {
+			Err(_) => panic!("Invalid input: {}", arg),
+			Ok(val) => val,
+		})
+		.collect();
+}
+
+
+// Run a program with the profiler enabled and report on it
+fn profile_program(path :&str, inputs :&[isize]) -> Result<String, IntcodeError>
+{
+	let mut program = load_program(path);
+	program.enable_profile();
+	program.extend_inputs(inputs.iter().cloned());
+	let result = program.run()?;
+	
+	let mut output = format!("Finished with: {:?}\nOutputs: {:?}\n\n", result, program.get_outputs());
+	if let Some(profile) = program.profile() {
+		output += &profile.report(program.memory(), PROFILE_TOP);
+	}
+	return Ok(output);
+}
+
+
+// Fuzz the engines against each other and describe what was found
+fn fuzz_engines(runs :&str, seed :Option<&String>) -> String
+{
+	let runs = match runs.parse() {
+		Err(_) => panic!("Invalid number of runs: {}", runs),
+		Ok(runs) => runs,
+	};
+	let seed = match seed {
+		Some(seed) => match seed.parse() {
+			Err(_) => panic!("Invalid seed: {}", seed),
+			Ok(seed) => seed,
+		},
+		None => std::time::SystemTime::now()
+			.duration_since(std::time::UNIX_EPOCH)
+			.map(|time| time.as_nanos() as u64)
+			.unwrap_or(0),
+	};
+	
+	let report = fuzz::fuzz(runs, seed);
+	let mut output = format!("Seed {}: ran {} programs for {} steps\n", seed, report.runs, report.steps);
+	match report.transpiled {
+		Ok(count) => output += &format!("Compared {} of them with the transpiled engine\n", count),
+		Err(error) => output += &format!("Couldn't use the transpiled engine: {}\n", error),
+	}
+	for failure in report.failures.iter() {
+		output += &format!("\n{}\nProgram: {}\nInputs: {:?}\n",
+			failure.difference, failure.case.source(), failure.case.inputs);
+	}
+	if report.failures.is_empty() {
+		output += "No differences found\n";
+	}
+	return output;
+}
+
+
+pub fn main(args :&[String])
+{
+	// Benchmarking and profiling take inputs after the file, and fuzzing takes
+	// a count and maybe a seed instead, everything else takes a single file
+	let max_args = match args[0].as_str() {
+		"bench" | "profile" => usize::MAX,
+		"fuzz" => 3,
+		_ => 2,
+	};
+	if args.len() < 2 || args.len() > max_args {
+		usage();
+		return;
+	}
+	
+	match args[0].as_str() {
+		"disasm" => {
+			let program = load_program(&args[1]);
+			print!("{}", disasm::listing(program.memory(), 0));
+		},
+		
+		"asm" => {
+			match asm::assemble(&load_file(&args[1])) {
+				Err(error) => panic!("Failed to assemble {}: {}", args[1], error),
+				Ok(program) => println!("{}", program),
+			}
+		},
+		
+		"cfg" => {
+			let program = load_program(&args[1]);
+			print!("{}", cfg::analyse(program.memory(), 0).to_dot());
+		},
+		
+		"debug" => {
+			let mut debugger = Debugger::new(load_program(&args[1]));
+			let stdin = io::stdin();
+			let stdout = io::stdout();
+			if let Err(error) = debugger.run(stdin.lock(), &mut stdout.lock()) {
+				panic!("Debugger failed: {}", error);
+			}
+		},
+		
+		"transpile" => {
+			let program = load_program(&args[1]);
+			print!("{}", transpile::transpile(program.memory()));
+		},
+		
+		"run" => run_program(&args[1], LineDevice::stdio()),
+		
+		"ascii" => run_program(&args[1], AsciiDevice::stdio()),
+		
+		"bench" => {
+			match bench_program(&args[1], &parse_inputs(&args[2..])) {
+				Err(error) => panic!("Program failed: {}", error),
+				Ok(report) => print!("{}", report),
+			}
+		},
+		
+		"profile" => {
+			match profile_program(&args[1], &parse_inputs(&args[2..])) {
+				Err(error) => panic!("Program failed: {}", error),
+				Ok(report) => print!("{}", report),
+			}
+		},
+		
+		"fuzz" => print!("{}", fuzz_engines(&args[1], args.get(2))),
+		
+		_ => usage(),
+	}
+}
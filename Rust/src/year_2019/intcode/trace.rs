@@ -0,0 +1,80 @@
+/**
+ * Log of executed instructions, for looking back at what a program did.
+ */
+
+use std::collections::{vec_deque, VecDeque};
+use std::fmt;
+use super::decode::Opcode;
+
+
+// A single executed instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+	pub pc : usize,
+	pub opcode : Opcode,
+	
+	// Operands after the modes have been applied, so values for anything that
+	// is read and the address for anything that is written
+	pub operands : [isize; 3],
+	
+	// The address and value written to memory, if any
+	pub write : Option<(usize, isize)>,
+}
+
+
+impl TraceEntry {
+	// The operands that the instruction actually uses
+	pub fn operands(self :&TraceEntry) -> &[isize] {
+		return &self.operands[..self.opcode.operand_count()];
+	}
+}
+
+
+impl fmt::Display for TraceEntry {
+	fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{:04}: {}", self.pc, self.opcode.mnemonic())?;
+		let operands :Vec<String> = self.operands().iter().map(|val| val.to_string()).collect();
+		if !operands.is_empty() {
+			write!(f, " {}", operands.join(", "))?;
+		}
+		if let Some((addr, val)) = self.write {
+			write!(f, " => [{}] = {}", addr, val)?;
+		}
+		return Ok(());
+	}
+}
+
+
+// Ring buffer of the most recent instructions
+#[derive(Debug, Clone)]
+pub struct Trace {
+	capacity : usize,
+	entries : VecDeque<TraceEntry>,
+}
+
+
+impl Trace {
+	pub fn new(capacity :usize) -> Trace {
+		return Trace {
+			capacity,
+			// Capacities can be large, so only grow as far as is needed
+			entries : VecDeque::new(),
+		};
+	}
+	
+	// Add an entry, dropping the oldest if we're full
+	pub fn push(self :&mut Trace, entry :TraceEntry) {
+		if self.capacity == 0 {
+			return;
+		}
+		if self.entries.len() == self.capacity {
+			self.entries.pop_front();
+		}
+		self.entries.push_back(entry);
+	}
+	
+	// Iterate over the entries, oldest first
+	pub fn entries<'a>(self :&'a Trace) -> vec_deque::Iter<'a, TraceEntry> {
+		return self.entries.iter();
+	}
+}
@@ -0,0 +1,457 @@
+/**
+ * Translates an Intcode program into Rust source, so that programs that get
+ * run over and over again don't have to be decoded every time.
+ *
+ * The generated Machine has the same interface as Interpreter (set, get,
+ * push_input, get_outputs, run) and dispatches with a match on the pc, with
+ * an arm for every instruction that could be found. Only the instruction
+ * words are baked in; operands are still read from memory, so patching them
+ * before running (like day 2 does) works as expected.
+ *
+ * Each arm checks that its instruction word hasn't changed before executing
+ * it, so a program that modifies its own code will fail with NotTranspiled
+ * rather than silently doing the wrong thing. The same goes for jumping to
+ * an address that doesn't have an arm.
+ *
+ * run_batch builds a set of programs into one binary with rustc and reports
+ * how each of them finished, for checking against the interpreter.
+ */
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use super::{cfg, IntcodeError, StepResult};
+use super::decode::{Instruction, Mode, Opcode};
+use super::disasm;
+use super::memory::MAX_DENSE_WORDS;
+
+
+// Counts the batches built so far, so that each gets its own directory
+static BATCHES :AtomicUsize = AtomicUsize::new(0);
+
+
+const PREAMBLE :&str = r#"// Generated by `aoc transpile`, do not edit.
+#![allow(dead_code, unused_parens, clippy::all)]
+
+use std::collections::VecDeque;
+
+
+pub const IMAGE :[isize; @LEN@] = [@IMAGE@];
+
+// Writes past here are an error, the same as for the interpreter
+pub const MAX_WORDS :usize = @MAX@;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepResult {
+	Continue,
+	Break,
+	Input,
+	Output(isize),
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntcodeError {
+	AddressOutOfRange { pc :usize, addr :isize },
+	NotTranspiled { pc :usize, ins :isize },
+	Overflow { pc :usize },
+}
+
+
+#[derive(Clone)]
+pub struct Machine {
+	mem : Vec<isize>,
+	pc : usize,
+	relative_base : isize,
+	inputs : VecDeque<isize>,
+	outputs : Vec<isize>,
+}
+
+
+impl Machine {
+	pub fn new() -> Machine {
+		return Machine {
+			mem : IMAGE.to_vec(),
+			pc : 0,
+			relative_base : 0,
+			inputs : VecDeque::new(),
+			outputs : Vec::new(),
+		};
+	}
+	
+	pub fn set(self :&mut Machine, idx :usize, val :isize) {
+		if idx >= self.mem.len() {
+			self.mem.resize(idx + 1, 0);
+		}
+		self.mem[idx] = val;
+	}
+	
+	pub fn get(self :&Machine, idx :usize) -> isize {
+		return match self.mem.get(idx) {
+			Some(val) => *val,
+			None => 0,
+		};
+	}
+	
+	pub fn push_input(self :&mut Machine, input :isize) {
+		self.inputs.push_back(input);
+	}
+	
+	pub fn extend_inputs<I>(self :&mut Machine, inputs :I)
+		where I :IntoIterator<Item = isize>
+	{
+		self.inputs.extend(inputs);
+	}
+	
+	pub fn get_outputs(self :&mut Machine) -> Vec<isize> {
+		return std::mem::take(&mut self.outputs);
+	}
+	
+	pub fn pc(self :&Machine) -> usize {
+		return self.pc;
+	}
+	
+	pub fn memory(self :&Machine) -> &[isize] {
+		return &self.mem;
+	}
+	
+	fn address(self :&Machine, pc :usize, addr :isize) -> Result<usize, IntcodeError> {
+		if addr < 0 {
+			return Err(IntcodeError::AddressOutOfRange { pc, addr });
+		}
+		return Ok(addr as usize);
+	}
+	
+	fn writable(self :&Machine, pc :usize, addr :isize) -> Result<usize, IntcodeError> {
+		let target = self.address(pc, addr)?;
+		if target >= MAX_WORDS {
+			return Err(IntcodeError::AddressOutOfRange { pc, addr });
+		}
+		return Ok(target);
+	}
+	
+	fn relative(self :&Machine, pc :usize, val :isize) -> Result<isize, IntcodeError> {
+		return self.relative_base.checked_add(val).ok_or(IntcodeError::Overflow { pc });
+	}
+	
+	fn check(self :&Machine, pc :usize, ins :isize) -> Result<(), IntcodeError> {
+		if self.get(pc) != ins {
+			return Err(IntcodeError::NotTranspiled { pc, ins : self.get(pc) });
+		}
+		return Ok(());
+	}
+	
+	pub fn run(self :&mut Machine) -> Result<StepResult, IntcodeError> {
+		loop {
+			match self.pc {
+"#;
+
+
+const POSTAMBLE :&str = r#"				pc => return Err(IntcodeError::NotTranspiled { pc, ins : self.get(pc) }),
+			}
+		}
+	}
+}
+"#;
+
+
+// Expression for reading the operand at addr
+fn read_expr(pc :usize, addr :usize, mode :Mode) -> String {
+	return match mode {
+		Mode::Position => format!("self.get(self.address({}, self.get({}))?)", pc, addr),
+		Mode::Immediate => format!("self.get({})", addr),
+		Mode::Relative => format!("self.get(self.address({0}, self.relative({0}, self.get({1}))?)?)", pc, addr),
+	};
+}
+
+
+// Expression for the address that the operand at addr writes to
+fn write_expr(pc :usize, addr :usize, mode :Mode) -> String {
+	return match mode {
+		Mode::Position => format!("self.writable({}, self.get({}))?", pc, addr),
+		Mode::Relative => format!("self.writable({0}, self.relative({0}, self.get({1}))?)?", pc, addr),
+		Mode::Immediate => unreachable!("Immediate writes are rejected by decode"),
+	};
+}
+
+
+// The body of the match arm for a single instruction
+fn translate(mem :&[isize], pc :usize, decoded :Instruction) -> Vec<String> {
+	let read = |idx :usize| read_expr(pc, pc + 1 + idx, decoded.modes[idx]);
+	let write = |idx :usize| write_expr(pc, pc + 1 + idx, decoded.modes[idx]);
+	let next = pc + decoded.size();
+	let mut body = vec![format!("self.check({}, {})?;", pc, mem[pc])];
+	let overflow = format!("ok_or(IntcodeError::Overflow {{ pc : {} }})?", pc);
+	
+	// Everything that stores a value works the same way
+	let binary = |op :&str| vec![
+		format!("let s0 = {};", read(0)),
+		format!("let s1 = {};", read(1)),
+		format!("let d0 = {};", write(2)),
+		format!("let val = {};", op),
+		"self.set(d0, val);".to_string(),
+		format!("self.pc = {};", next),
+	];
+	let jump = |cond :&str| vec![
+		format!("let s0 = {};", read(0)),
+		format!("let d0 = {};", read(1)),
+		format!("if s0 {} 0 {{ self.pc = self.address({}, d0)?; }} else {{ self.pc = {}; }}", cond, pc, next),
+	];
+	
+	body.extend(match decoded.opcode {
+		Opcode::Add => binary(&format!("s0.checked_add(s1).{}", overflow)),
+		Opcode::Mul => binary(&format!("s0.checked_mul(s1).{}", overflow)),
+		Opcode::LessThan => binary("if s0 < s1 { 1 } else { 0 }"),
+		Opcode::Equals => binary("if s0 == s1 { 1 } else { 0 }"),
+		Opcode::JumpNonZero => jump("!="),
+		Opcode::JumpZero => jump("=="),
+		Opcode::Input => vec![
+			"let input = match self.inputs.front() { Some(&input) => input, None => return Ok(StepResult::Input) };".to_string(),
+			format!("let d0 = {};", write(0)),
+			"self.inputs.pop_front();".to_string(),
+			"self.set(d0, input);".to_string(),
+			format!("self.pc = {};", next),
+		],
+		Opcode::Output => vec![
+			format!("let s0 = {};", read(0)),
+			"self.outputs.push(s0);".to_string(),
+			format!("self.pc = {};", next),
+		],
+		Opcode::AdjustBase => vec![
+			format!("let s0 = {};", read(0)),
+			format!("self.relative_base = self.relative({}, s0)?;", pc),
+			format!("self.pc = {};", next),
+		],
+		Opcode::Halt => vec![
+			format!("self.pc = {};", next),
+			"return Ok(StepResult::Break);".to_string(),
+		],
+	});
+	return body;
+}
+
+
+// Generate Rust source for a machine that runs the program
+pub fn transpile(mem :&[isize]) -> String {
+	// Take everything that's reachable, plus anything that a straight read
+	// through decodes, since indirect jumps (like returning from a function)
+	// usually land just after where the call was made
+	let graph = cfg::analyse(mem, 0);
+	let mut instructions :BTreeMap<usize, Instruction> = BTreeMap::new();
+	let reachable = graph.blocks.values().flat_map(|block| block.lines.iter());
+	let lines :Vec<disasm::Line> = disasm::disassemble(mem, 0).collect();
+	for line in reachable.chain(lines.iter()) {
+		if let Ok(decoded) = Instruction::decode(line.addr, mem[line.addr]) {
+			if line.addr + decoded.size() <= mem.len() {
+				instructions.insert(line.addr, decoded);
+			}
+		}
+	}
+	
+	// Stick the image and the fixed parts of the machine around the arms
+	let image :Vec<String> = mem.iter().map(|val| val.to_string()).collect();
+	let mut output = PREAMBLE
+		.replace("@LEN@", &mem.len().to_string())
+		.replace("@IMAGE@", &image.join(", "))
+		.replace("@MAX@", &MAX_DENSE_WORDS.to_string());
+	for (&pc, &decoded) in instructions.iter() {
+		output += &format!("\t\t\t\t{} => {{\n", pc);
+		for line in translate(mem, pc, decoded) {
+			output += &format!("\t\t\t\t\t{}\n", line);
+		}
+		output += "\t\t\t\t},\n";
+	}
+	output += POSTAMBLE;
+	
+	// Note anything that might stop it from working
+	for issue in graph.issues.iter() {
+		output += &format!("// {}\n", issue);
+	}
+	return output;
+}
+
+
+// A program to build and run, with its inputs and the addresses to report on
+// once it stops
+pub struct Run {
+	pub program : Vec<isize>,
+	pub inputs : Vec<isize>,
+	pub addresses : Vec<usize>,
+}
+
+
+// How a program finished, in the same form that run_batch gets back from the
+// transpiled binary. Errors only have the fields that it knows about.
+pub fn summary(result :Option<&Result<StepResult, IntcodeError>>, outputs :&[isize], memory :&[(usize, isize)]) -> String {
+	let status = match result {
+		Some(Ok(result)) => format!("{:?}", result),
+		Some(&Err(IntcodeError::Overflow { pc, .. })) => format!("Overflow {{ pc: {} }}", pc),
+		Some(&Err(IntcodeError::AddressOutOfRange { pc, addr, .. })) =>
+			format!("AddressOutOfRange {{ pc: {}, addr: {} }}", pc, addr),
+		Some(Err(error)) => format!("{:?}", error),
+		None => "None".to_string(),
+	};
+	return format!("{};{:?};{:?}", status, outputs, memory);
+}
+
+
+// Build the transpiled form of each program into a single binary and run it,
+// getting back the summary that each one printed
+pub fn run_batch(runs :&[Run]) -> Result<Vec<String>, String> {
+	let mut source = String::new();
+	let mut main = String::from("fn main() {\n");
+	for (idx, run) in runs.iter().enumerate() {
+		source += &format!("mod p{} {{\n{}}}\n\n", idx, transpile(&run.program));
+		main += &format!("\t{{\n\t\tlet mut machine = p{}::Machine::new();\n", idx);
+		main += &format!("\t\tlet inputs :Vec<isize> = vec!{:?};\n", run.inputs);
+		main += &format!("\t\tlet addresses :Vec<usize> = vec!{:?};\n", run.addresses);
+		main += "\t\tmachine.extend_inputs(inputs);\n";
+		main += "\t\tlet status = match machine.run() { Ok(result) => format!(\"{:?}\", result), Err(error) => format!(\"{:?}\", error) };\n";
+		main += "\t\tlet memory :Vec<(usize, isize)> = addresses.into_iter().map(|addr| (addr, machine.get(addr))).collect();\n";
+		main += "\t\tprintln!(\"{};{:?};{:?}\", status, machine.get_outputs(), memory);\n\t}\n";
+	}
+	source += &main;
+	source += "}\n";
+	
+	// Build it somewhere out of the way
+	let batch = BATCHES.fetch_add(1, Ordering::SeqCst);
+	let dir = std::env::temp_dir().join(format!("aoc-transpile-{}-{}", std::process::id(), batch));
+	fs::create_dir_all(&dir).map_err(|error| format!("Failed to create {:?}: {}", dir, error))?;
+	let path = dir.join("batch.rs");
+	let binary = dir.join("batch");
+	fs::write(&path, source).map_err(|error| format!("Failed to write {:?}: {}", path, error))?;
+	let build = Command::new("rustc")
+		.arg("--edition").arg("2018")
+		.arg("-o").arg(&binary)
+		.arg(&path)
+		.output()
+		.map_err(|error| format!("Failed to run rustc: {}", error))?;
+	if !build.status.success() {
+		return Err(format!("Transpiled source in {:?} failed to build:\n{}", path, String::from_utf8_lossy(&build.stderr)));
+	}
+	let output = Command::new(&binary)
+		.output()
+		.map_err(|error| format!("Failed to run {:?}: {}", binary, error))?;
+	let _ = fs::remove_dir_all(&dir);
+	
+	let lines :Vec<String> = String::from_utf8_lossy(&output.stdout).lines().map(|line| line.to_string()).collect();
+	if lines.len() != runs.len() {
+		return Err(format!("Transpiled binary only got through {} of {} programs", lines.len(), runs.len()));
+	}
+	return Ok(lines);
+}
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::Interpreter;
+	
+	// The example programs from days 2, 5, 7 and 9, with inputs to run them on
+	const EXAMPLES :[(&str, &[isize]); 27] = [
+		("1,9,10,3,2,3,11,0,99,30,40,50", &[]),
+		("1,0,0,0,99", &[]),
+		("2,3,0,3,99", &[]),
+		("2,4,4,5,99,0", &[]),
+		("3,0,4,0,99", &[7]),
+		("3,9,8,9,10,9,4,9,99,-1,8", &[8]),
+		("3,9,8,9,10,9,4,9,99,-1,8", &[5]),
+		("3,9,7,9,10,9,4,9,99,-1,8", &[5]),
+		("3,9,7,9,10,9,4,9,99,-1,8", &[8]),
+		("3,3,1108,-1,8,3,4,3,99", &[8]),
+		("3,3,1108,-1,8,3,4,3,99", &[9]),
+		("3,3,1107,-1,8,3,4,3,99", &[8]),
+		("3,3,1107,-1,8,3,4,3,99", &[9]),
+		("3,12,6,12,15,1,13,14,13,4,13,99,-1,0,1,9", &[0]),
+		("3,3,1105,-1,9,1101,0,0,12,4,12,99,1", &[3]),
+		("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,\
+			104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99", &[7]),
+		("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,\
+			104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99", &[8]),
+		("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,\
+			104,999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99", &[9]),
+		("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0", &[4, 0]),
+		("3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0", &[1, 4]),
+		("3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0", &[1, 0]),
+		("3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5", &[9, 0]),
+		("3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,\
+			1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10", &[9, 0]),
+		("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99", &[]),
+		("1102,34915192,34915192,7,4,7,99,0", &[]),
+		("104,1125899906842624,99", &[]),
+		// Without the base of 2000 that the puzzle starts it from, this reads
+		// from a negative address
+		("109,19,204,-34,99", &[]),
+	];
+	
+	// Examples that overwrite an instruction before running it, which the
+	// transpiled machine refuses to do
+	const SELF_MODIFYING :[&str; 3] = [
+		"1,1,1,4,99,5,6,0,99",
+		"1002,4,3,4,33",
+		"1101,100,-1,4,0",
+	];
+	
+	// Day 9's samples, and an add and a multiply that overflow, along with
+	// how each one should finish
+	const EXPECTED :[(&str, &str); 5] = [
+		("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99",
+			"Break;[109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99];[]"),
+		("1102,34915192,34915192,7,4,7,99,0", "Break;[1219070632396864];[]"),
+		("104,1125899906842624,99", "Break;[1125899906842624];[]"),
+		("1101,9223372036854775807,1,5,99,0", "Overflow { pc: 0 };[];[]"),
+		("104,1,1102,4611686018427387904,2,7,99,0", "Overflow { pc: 2 };[1];[]"),
+	];
+	
+	#[test]
+	fn expected_results() {
+		let runs :Vec<Run> = EXPECTED.iter()
+			.map(|&(source, _)| Run {
+				program : Interpreter::load(source).unwrap().memory().to_vec(),
+				inputs : Vec::new(),
+				addresses : Vec::new(),
+			})
+			.collect();
+		let lines = run_batch(&runs).unwrap();
+		for (&(source, expected), line) in EXPECTED.iter().zip(lines.iter()) {
+			assert_eq!(line, expected, "{}", source);
+		}
+	}
+	
+	#[test]
+	fn examples_match_interpreter() {
+		// Run everything on the interpreter first, to find out how far each
+		// program's memory grows
+		let mut expected = Vec::new();
+		let mut runs = Vec::new();
+		for &(source, inputs) in EXAMPLES.iter() {
+			let mut machine = Interpreter::load(source).unwrap();
+			machine.extend_inputs(inputs.iter().cloned());
+			let result = machine.run();
+			let memory :Vec<(usize, isize)> = (0..machine.memory().len()).map(|addr| (addr, machine.get(addr))).collect();
+			expected.push(summary(Some(&result), &machine.get_outputs(), &memory));
+			runs.push(Run {
+				program : Interpreter::load(source).unwrap().memory().to_vec(),
+				inputs : inputs.to_vec(),
+				addresses : memory.iter().map(|&(addr, _)| addr).collect(),
+			});
+		}
+		for source in SELF_MODIFYING.iter() {
+			runs.push(Run {
+				program : Interpreter::load(source).unwrap().memory().to_vec(),
+				inputs : Vec::new(),
+				addresses : Vec::new(),
+			});
+		}
+		
+		let lines = run_batch(&runs).unwrap();
+		for ((&(source, _), expected), line) in EXAMPLES.iter().zip(expected.iter()).zip(lines.iter()) {
+			assert_eq!(line, expected, "{}", source);
+		}
+		for (source, line) in SELF_MODIFYING.iter().zip(lines.iter().skip(EXAMPLES.len())) {
+			assert!(line.starts_with("NotTranspiled"), "{} finished with {}", source, line);
+		}
+	}
+}
@@ -0,0 +1,69 @@
+/**
+ * Log of what each executed instruction changed, so that a program can be
+ * stepped backwards to see how it got to where it is.
+ *
+ * Each entry holds whatever is needed to put the machine back how it was
+ * before the instruction ran: the pc and relative base, the old value of any
+ * word it overwrote, any input it consumed and where any output it produced
+ * was saved. Only the most recent entries are kept, so a long run can only be
+ * undone so far.
+ */
+
+use std::collections::VecDeque;
+
+
+// What a single instruction changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndoEntry {
+	pub pc : usize,
+	pub relative_base : isize,
+	
+	// The address written to and the value that was there before
+	pub write : Option<(usize, isize)>,
+	
+	// The input value that was read
+	pub input : Option<isize>,
+	
+	// Where the output was saved, if it was kept by the interpreter rather
+	// than handed straight back to the caller
+	pub output : Option<usize>,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct UndoLog {
+	capacity : usize,
+	entries : VecDeque<UndoEntry>,
+}
+
+
+impl UndoLog {
+	pub fn new(capacity :usize) -> UndoLog {
+		return UndoLog {
+			capacity,
+			// Capacities can be large, so only grow as far as is needed
+			entries : VecDeque::new(),
+		};
+	}
+	
+	// Add an entry, forgetting the oldest if we're full
+	pub fn push(self :&mut UndoLog, entry :UndoEntry) {
+		if self.capacity == 0 {
+			return;
+		}
+		if self.entries.len() == self.capacity {
+			self.entries.pop_front();
+		}
+		self.entries.push_back(entry);
+	}
+	
+	// Take off the most recent entry, to be undone
+	pub fn pop(self :&mut UndoLog) -> Option<UndoEntry> {
+		return self.entries.pop_back();
+	}
+	
+	// Get the most recent entry, to fill in what happened after it was added
+	pub fn last_mut(self :&mut UndoLog) -> Option<&mut UndoEntry> {
+		return self.entries.back_mut();
+	}
+}
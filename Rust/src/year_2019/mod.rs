@@ -1,4 +1,5 @@
 // Modules that the days can use
+mod combinatorics;
 mod intcode;
 
 // Days
@@ -22,3 +23,9 @@ pub fn main() {
 	day_7::main();
 	day_8::main();
 }
+
+
+// Entry for the Intcode tooling
+pub fn tools(args :&[String]) {
+	intcode::tools::main(args);
+}